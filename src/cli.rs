@@ -1,5 +1,7 @@
+use std::path::PathBuf;
+
 use clap::{Args, Parser, Subcommand};
-use crate::workspace_state::Protocol;
+use crate::workspace_state::{Protocol, TilingState, WorkspaceOrder};
 
 
 #[derive(Parser, Debug)]
@@ -14,7 +16,17 @@ pub struct Cli {
 #[derive(Args, Debug)]
 pub struct GlobalOpts {
     #[clap(long)]
-    pub protocol: Option<Protocol>
+    pub protocol: Option<Protocol>,
+    #[clap(long, help = "Print errors as json object to stderr. Also switches list output to json.")]
+    pub json: bool,
+    #[clap(long, help = "Wait until the compositor applied the change and fail if it did not.")]
+    pub wait: bool,
+    #[clap(long, value_name = "MS", default_value_t = 1000, help = "How long --wait waits for the change. Also how long create-workspace --print-id, move-focus --create, apply and restore wait for new workspaces.")]
+    pub wait_timeout: u64,
+    #[clap(long, help = "Connect to the compositor directly even if a daemon is running.")]
+    pub no_daemon: bool,
+    #[clap(long, value_enum, default_value_t, help = "Order of workspaces in list, for --index and for next/prev.")]
+    pub order: WorkspaceOrder,
 }
 
 #[derive(Subcommand, Debug)]
@@ -29,12 +41,14 @@ pub enum Commands {
         about = "Deactivate selected workspace. Some options require an output selection."
     )]
     Deactivate(WorkspaceArgs),
-    #[clap(visible_alias = "s", about = "Assign workspace to selected output.")]
+    #[clap(visible_alias = "s", about = "Assign workspace to selected output. Requires the assign capability (ext-v1).")]
     Assign{
         #[command(flatten)]
         workspace_args: WorkspaceArgs,
         #[command(flatten)]
-        target: TargetOutput 
+        target: TargetOutput,
+        #[clap(long, help = "Also activate the workspace on its new output.")]
+        follow: bool,
     },
     #[clap(
         visible_alias = "r",
@@ -47,14 +61,113 @@ pub enum Commands {
         workspace_name: String,
         #[command(flatten)]
         output: OutputSelector,
+        #[clap(long, help = "Wait for the workspace and print its stable id. Requires ext-v1.")]
+        print_id: bool,
+    },
+    #[clap(about = "Rename selected workspace. Requires the rename capability (cosmic-v1 version 2).")]
+    Rename {
+        #[command(flatten)]
+        workspace_args: WorkspaceArgs,
+        #[clap(long)]
+        new_name: String,
+    },
+    #[clap(about = "Set tiling state of selected workspace. Requires the set_tiling_state capability (cosmic-v1 version 2).")]
+    SetTilingState {
+        #[command(flatten)]
+        workspace_args: WorkspaceArgs,
+        #[clap(value_enum)]
+        tiling_state: TilingState,
+    },
+    #[clap(about = "Activate the workspace after the active one on the selected output, in list order.")]
+    Next(NavigationArgs),
+    #[clap(about = "Activate the workspace before the active one on the selected output, in list order.")]
+    Prev(NavigationArgs),
+    #[clap(about = "Activate the nearest workspace in a direction on the coordinate grid of the selected output.")]
+    MoveFocus {
+        #[clap(value_enum)]
+        direction: Direction,
+        #[command(flatten)]
+        output: Option<OutputSelector>,
+        #[clap(long, value_name = "NAME", help = "Create and activate a workspace with this name when the edge is reached.")]
+        create: Option<String>,
+    },
+    #[clap(about = "Wait until the selected workspace, or any workspace on the selected output, is in a state.")]
+    Wait(WaitArgs),
+    #[clap(about = "Read commands from stdin or a file, one per line, and apply them together in a single commit.")]
+    Batch {
+        #[clap(value_name = "FILE", help = "Read commands from this file instead of stdin.")]
+        file: Option<PathBuf>,
+    },
+    #[clap(about = "Create, assign and optionally remove workspaces to match a layout file (TOML, or JSON with .json extension).")]
+    Apply {
+        #[clap(value_name = "FILE")]
+        file: PathBuf,
+        #[clap(long, help = "Print the planned requests instead of sending them.")]
+        dry_run: bool,
+        #[clap(long, help = "Remove workspaces on the listed outputs that are not in the layout.")]
+        remove_extra: bool,
+    },
+    #[clap(about = "Save outputs and workspaces to a file that restore reads back.")]
+    Save {
+        #[clap(value_name = "FILE")]
+        file: PathBuf,
+    },
+    #[clap(about = "Create, assign and activate workspaces to reproduce a file written by save.")]
+    Restore {
+        #[clap(value_name = "FILE")]
+        file: PathBuf,
+        #[clap(long, help = "Print the planned requests instead of sending them.")]
+        dry_run: bool,
     },
     #[clap(
         visible_alias = "ls",
         about = "List workspaces. Global or on selected output."
     )]
     List(ListArgs),
-    #[clap(hide = true)]
-    Listen,
+    #[clap(about = "Run the commands configured in the [hooks] section of the config file on workspace and output changes.")]
+    Hooks {
+        #[clap(
+            long,
+            value_name = "FILE",
+            help = "Config file instead of $XDG_CONFIG_HOME/wsctrl/config.toml."
+        )]
+        config: Option<PathBuf>,
+    },
+    #[clap(about = "Keep the connection to the compositor and serve the commands of other wsctrl calls over a socket in $XDG_RUNTIME_DIR.")]
+    Daemon,
+    #[clap(
+        alias = "listen",
+        about = "Print changes of workspaces and outputs as json lines until the compositor finishes the workspace manager."
+    )]
+    Watch {
+        #[clap(long, help = "Print the complete state after each change instead of the changes.")]
+        full: bool,
+        #[clap(
+            long,
+            value_name = "FILE",
+            help = "Also run the commands configured in the [hooks] section of this config file."
+        )]
+        config: Option<PathBuf>,
+    },
+}
+
+/// A single line of a batch. Takes the same subcommands as the command line, without the binary name
+/// and global options, e.g. `activate --name web`.
+#[derive(Parser, Debug)]
+#[command(no_binary_name = true)]
+pub struct BatchLine {
+    #[command(subcommand)]
+    pub command: Commands,
+}
+
+/// Left/right along the horizontal axis, up/down along the vertical one: workspace coordinates for
+/// move-focus, output positions for assign.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Direction {
+    Left,
+    Right,
+    Up,
+    Down,
 }
 
 #[derive(Args, Debug, Clone)]
@@ -67,38 +180,163 @@ pub struct ListArgs {
     pub json: bool
 }
 
+#[derive(Args, Debug, Clone)]
+pub struct NavigationArgs {
+    #[command(flatten)]
+    pub output: Option<OutputSelector>,
+    #[clap(long, overrides_with = "no_wrap", help = "Continue at the other end of the output (default).")]
+    pub wrap: bool,
+    #[clap(long, overrides_with = "wrap", help = "Stop at the first/last workspace of the output.")]
+    pub no_wrap: bool,
+    #[clap(long, help = "Skip workspaces in hidden state.")]
+    pub skip_hidden: bool,
+}
+
+impl NavigationArgs {
+    pub fn wrap(&self) -> bool {
+        !self.no_wrap
+    }
+}
+
 #[derive(Args, Debug, Clone)]
 pub struct WorkspaceArgs {
     #[command(flatten)]
     pub workspace: WorkspaceSelector,
     #[command(flatten)]
+    pub filter: StateFilter,
+    #[command(flatten)]
     pub output: Option<OutputSelector>,
+    #[clap(long, help = "Apply to every matching workspace, in a single commit. Without it several matches are an error.")]
+    pub all: bool,
 }
 
-const WORKSPACE_SELECTION_HELP_HEADING: &str = "Workspace selection (mutually exclusive options)";
+const STATE_FILTER_HELP_HEADING: &str = "State filter (combined with the workspace selection)";
+#[derive(Args, Debug, Clone, Default)]
+pub struct StateFilter {
+    #[clap(long, help_heading = STATE_FILTER_HELP_HEADING)]
+    pub urgent: bool,
+    #[clap(long, help_heading = STATE_FILTER_HELP_HEADING)]
+    pub hidden: bool,
+    #[clap(long, help_heading = STATE_FILTER_HELP_HEADING)]
+    pub inactive: bool,
+}
+
+impl StateFilter {
+    pub fn is_empty(&self) -> bool {
+        !(self.urgent || self.hidden || self.inactive)
+    }
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct WaitArgs {
+    #[command(flatten)]
+    pub workspace: Option<WorkspaceMatch>,
+    #[command(flatten)]
+    pub output: Option<OutputSelector>,
+    #[command(flatten)]
+    pub condition: WaitCondition,
+    #[clap(long, value_name = "MS", help = "Fail if the condition does not hold in time. Waits forever without.")]
+    pub timeout: Option<u64>,
+}
+
+const WAIT_CONDITION_HELP_HEADING: &str = "Condition (mutually exclusive options)";
 #[derive(Args, Debug, Clone)]
 #[group(required = true, multiple = false)]
+pub struct WaitCondition {
+    #[clap(long, help_heading = WAIT_CONDITION_HELP_HEADING)]
+    pub exists: bool,
+    #[clap(long, help_heading = WAIT_CONDITION_HELP_HEADING)]
+    pub active: bool,
+    #[clap(long, help_heading = WAIT_CONDITION_HELP_HEADING)]
+    pub urgent: bool,
+    #[clap(long, help_heading = WAIT_CONDITION_HELP_HEADING, help = "No matching workspace exists.")]
+    pub removed: bool,
+}
+
+impl WorkspaceSelector {
+    pub fn is_empty(&self) -> bool {
+        !self.active && self.matching.is_empty()
+    }
+}
+
+impl WorkspaceMatch {
+    pub fn is_empty(&self) -> bool {
+        self.index.is_none()
+            && self.name.is_none()
+            && self.name_match.is_none()
+            && self.name_regex.is_none()
+            && self.protocol_id.is_none()
+            && self.coordinates.is_none()
+            && self.id.is_none()
+    }
+}
+
+const WORKSPACE_SELECTION_HELP_HEADING: &str = "Workspace selection (mutually exclusive options)";
+// not required by clap, a state filter alone also selects workspaces
+#[derive(Args, Debug, Clone, Default)]
 pub struct WorkspaceSelector {
-    #[clap(short, long, help_heading = WORKSPACE_SELECTION_HELP_HEADING, requires = "output", help = "Requires output selection.")]
+    #[clap(short, long, help_heading = WORKSPACE_SELECTION_HELP_HEADING, requires = "output", conflicts_with = "workspace_match", help = "Requires output selection.")]
     pub active: bool,
-    #[clap(short, long, help_heading = WORKSPACE_SELECTION_HELP_HEADING, help = "Workspaces are ordered by wayland protocol id. Global or on selected output.")]
-    pub index: Option<usize>,
+    #[command(flatten)]
+    pub matching: WorkspaceMatch,
+}
+
+/// Workspace selection by properties of the workspace. Also used by `wait`, where `--active` is a
+/// condition.
+#[derive(Args, Debug, Clone, Default)]
+#[group(id = "workspace_match", required = false, multiple = false)]
+pub struct WorkspaceMatch {
+    #[clap(short, long, value_parser = parse_index, allow_negative_numbers = true, help_heading = WORKSPACE_SELECTION_HELP_HEADING, help = "Position as printed by list, starting at 1, -1 is the last. Global or on selected output.")]
+    pub index: Option<i32>,
     #[clap(short, long, help_heading = WORKSPACE_SELECTION_HELP_HEADING, help = "Global or on selected output.")]
     pub name: Option<String>,
+    #[clap(long, value_name = "GLOB", help_heading = WORKSPACE_SELECTION_HELP_HEADING, help = "Names matching the pattern, * matches any text and ? a single character.")]
+    pub name_match: Option<String>,
+    #[clap(long, value_name = "REGEX", help_heading = WORKSPACE_SELECTION_HELP_HEADING, help = "Names containing a match of the regular expression.")]
+    pub name_regex: Option<String>,
     #[clap(short, long, value_name = "ID", help_heading = WORKSPACE_SELECTION_HELP_HEADING, help = "Wayland protocol id used in communication between server and client.")]
     pub protocol_id: Option<usize>,
     #[clap(short, long, value_delimiter = ',', num_args = 1.., value_name = "COORDS", help_heading = WORKSPACE_SELECTION_HELP_HEADING, requires = "output", help = "Coordinate space depends on compositor. Requires output selection.")]
-    pub coordinates: Option<Vec<u8>>,
+    pub coordinates: Option<Vec<u32>>,
+    #[clap(long, help_heading = WORKSPACE_SELECTION_HELP_HEADING, help = "Stable id that stays the same across connections. Requires ext-v1.")]
+    pub id: Option<String>,
 }
 
 const OUTPUT_SELECTION_HELP_HEADING: &str = "Output selection (mutually exclusive options)";
-#[derive(Args, Debug, Clone)]
+#[derive(Args, Debug, Clone, Default)]
 #[group(id = "output", required = false, multiple = false)]
 pub struct OutputSelector {
     #[clap(short = 'o', long, help_heading = OUTPUT_SELECTION_HELP_HEADING)]
     pub output_name: Option<String>,
     #[clap(short = 'u', long, value_name = "OUTPUT_ID", help_heading = OUTPUT_SELECTION_HELP_HEADING)]
     pub output_protocol_id: Option<usize>,
+    #[clap(long, value_name = "MAKE", help_heading = OUTPUT_SELECTION_HELP_HEADING)]
+    pub output_make: Option<String>,
+    #[clap(long, value_name = "MODEL", help_heading = OUTPUT_SELECTION_HELP_HEADING)]
+    pub output_model: Option<String>,
+    #[clap(long, value_name = "TEXT", help_heading = OUTPUT_SELECTION_HELP_HEADING, help = "Part of the output description, which usually contains make, model and serial number.")]
+    pub output_description: Option<String>,
+    #[clap(long, value_name = "X,Y", value_parser = parse_position, allow_hyphen_values = true, help_heading = OUTPUT_SELECTION_HELP_HEADING, help = "Output covering this position in the compositor space.")]
+    pub output_at: Option<(i32, i32)>,
+    #[clap(long, help_heading = OUTPUT_SELECTION_HELP_HEADING)]
+    pub output_leftmost: bool,
+    #[clap(long, help_heading = OUTPUT_SELECTION_HELP_HEADING)]
+    pub output_rightmost: bool,
+    #[clap(long, value_name = "INDEX", value_parser = parse_index, allow_negative_numbers = true, help_heading = OUTPUT_SELECTION_HELP_HEADING, help = "Outputs are ordered by position, from left to right and top to bottom. Starts at 1, -1 is the last.")]
+    pub output_index: Option<i32>,
+}
+
+fn parse_index(value: &str) -> Result<i32, String> {
+    match value.parse::<i32>().map_err(|e| e.to_string())? {
+        0 => Err("indices start at 1 (not 0 anymore), -1 is the last".to_string()),
+        index => Ok(index),
+    }
+}
+
+fn parse_position(value: &str) -> Result<(i32, i32), String> {
+    let (x, y) = value.split_once(',').ok_or("expected X,Y")?;
+    let coordinate = |c: &str| c.trim().parse::<i32>().map_err(|e| format!("{c}: {e}"));
+    Ok((coordinate(x)?, coordinate(y)?))
 }
 
 // same as OutputSelector, just needs a different name because assign command might require output selection twice
@@ -111,6 +349,10 @@ pub struct TargetOutput {
     pub target_output_name: Option<String>,
     #[clap(short = 'r', long, value_name = "TARGET_ID", help_heading = TARGET_OUTPUT_HELP_HEADING)]
     pub target_output_protocol_id: Option<usize>,
+    #[clap(long, value_enum, help_heading = TARGET_OUTPUT_HELP_HEADING, help = "Output next to the one of the workspace, by position in the compositor space.")]
+    pub direction: Option<Direction>,
+    #[clap(long, help_heading = TARGET_OUTPUT_HELP_HEADING, help = "Output after the one of the workspace, in the order of --output-index. Wraps around.")]
+    pub next_output: bool,
 }
 
 impl TargetOutput {
@@ -118,6 +360,7 @@ impl TargetOutput {
         OutputSelector{
             output_name: self.target_output_name.clone(),
             output_protocol_id: self.target_output_protocol_id,
+            ..Default::default()
         }
     }
 }
\ No newline at end of file
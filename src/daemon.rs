@@ -0,0 +1,172 @@
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, BufReader, ErrorKind, Write};
+use std::os::fd::AsFd;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error;
+use crate::workspace_manager::WorkspaceClient;
+
+/// Command line of a wsctrl call, without the binary name. Sent as a single json line.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Request {
+    pub args: Vec<String>,
+}
+
+/// Answer to a [`Request`], sent as a single json line.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Response {
+    /// The daemon ran the command. `output` is what the command printed to stdout.
+    Done { output: String, error: Option<Error> },
+    /// The daemon does not run the command, the client connects to the compositor itself.
+    Unsupported,
+}
+
+impl From<Result<String, Error>> for Response {
+    fn from(result: Result<String, Error>) -> Self {
+        match result {
+            Ok(output) => Response::Done {
+                output,
+                error: None,
+            },
+            Err(e) => Response::Done {
+                output: String::new(),
+                error: Some(e),
+            },
+        }
+    }
+}
+
+/// Socket of the daemon for the compositor in `WAYLAND_DISPLAY`, in `XDG_RUNTIME_DIR`.
+pub fn socket_path() -> Result<PathBuf, Error> {
+    let dir = env::var_os("XDG_RUNTIME_DIR")
+        .ok_or(Error::InvalidArgument("XDG_RUNTIME_DIR is not set".to_string()))?;
+    let display = env::var("WAYLAND_DISPLAY").unwrap_or("wayland-0".to_string());
+    // WAYLAND_DISPLAY may also be an absolute path
+    Ok(Path::new(&dir).join(format!("wsctrl-{}.sock", display.replace('/', "_"))))
+}
+
+/// Serves requests on the socket until the compositor finishes the workspace manager. `handle`
+/// runs the command line of a request with the client, whose state is kept up to date between
+/// requests.
+pub fn serve(
+    client: &mut WorkspaceClient,
+    mut handle: impl FnMut(&mut WorkspaceClient, Vec<String>) -> Response,
+) -> Result<(), Error> {
+    let path = socket_path()?;
+    let listener = bind(&path)?;
+    let _socket = RemoveOnDrop(&path);
+    info!("serving workspace requests on {}", path.display());
+    while !client.is_finished() {
+        if !client.dispatch_or_readable(listener.as_fd())? {
+            continue;
+        }
+        match listener.accept() {
+            Ok((stream, _)) => {
+                if let Err(e) = respond(client, stream, &mut handle) {
+                    warn!("unable to answer request: {e}");
+                }
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => {}
+            Err(e) => warn!("unable to accept connection: {e}"),
+        }
+    }
+    info!("compositor finished the workspace manager, stop serving");
+    Ok(())
+}
+
+/// Runs the command line in a daemon. `None` if no daemon is running, it does not serve the
+/// command or it does not answer, the caller then connects to the compositor itself.
+pub fn forward(args: &[String]) -> Option<Result<String, Error>> {
+    let path = socket_path().ok()?;
+    let stream = UnixStream::connect(&path).ok()?;
+    debug!("forwarding request to daemon on {}", path.display());
+    let line = match exchange(stream, args) {
+        Ok(line) => line,
+        Err(e) => {
+            warn!("daemon on {} did not answer, connecting directly: {e}", path.display());
+            return None;
+        }
+    };
+    let response = serde_json::from_str(&line)
+        .map_err(|e| Error::Connection(format!("Daemon connection failed: {e}")));
+    match response {
+        Ok(Response::Done {
+            output,
+            error: None,
+        }) => Some(Ok(output)),
+        Ok(Response::Done {
+            error: Some(e), ..
+        }) => Some(Err(e)),
+        Ok(Response::Unsupported) => None,
+        Err(e) => Some(Err(e)),
+    }
+}
+
+fn bind(path: &Path) -> Result<UnixListener, Error> {
+    if UnixStream::connect(path).is_ok() {
+        return Err(Error::InvalidArgument(format!(
+            "A daemon is already serving on {}",
+            path.display()
+        )));
+    }
+    // left behind by a daemon that was killed
+    let _ = fs::remove_file(path);
+    let listener = UnixListener::bind(path)
+        .map_err(|e| Error::Connection(format!("Unable to listen on {}: {e}", path.display())))?;
+    listener
+        .set_nonblocking(true)
+        .map_err(|e| Error::Connection(format!("Unable to configure {}: {e}", path.display())))?;
+    Ok(listener)
+}
+
+fn respond(
+    client: &mut WorkspaceClient,
+    stream: UnixStream,
+    handle: &mut impl FnMut(&mut WorkspaceClient, Vec<String>) -> Response,
+) -> io::Result<()> {
+    // a stuck client must not block the daemon
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(Duration::from_secs(1)))?;
+    stream.set_write_timeout(Some(Duration::from_secs(1)))?;
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line)?;
+    let request: Request = serde_json::from_str(&line)?;
+    debug!("request {:?}", request.args);
+    let response = handle(client, request.args);
+    send(&stream, &response)
+}
+
+/// Sends the request and reads the response line. Fails only if nothing of the response arrived,
+/// e.g. the daemon exited or dropped the connection, so the request can be retried directly.
+fn exchange(stream: UnixStream, args: &[String]) -> io::Result<String> {
+    send(&stream, &Request {
+        args: args.to_vec(),
+    })?;
+    let mut line = String::new();
+    match BufReader::new(&stream).read_line(&mut line) {
+        Ok(0) => Err(io::Error::new(ErrorKind::UnexpectedEof, "connection closed")),
+        Err(e) if line.is_empty() => Err(e),
+        _ => Ok(line),
+    }
+}
+
+fn send(mut stream: &UnixStream, message: &impl Serialize) -> io::Result<()> {
+    let mut line = serde_json::to_string(message)?;
+    line.push('\n');
+    stream.write_all(line.as_bytes())
+}
+
+struct RemoveOnDrop<'a>(&'a Path);
+
+impl Drop for RemoveOnDrop<'_> {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(self.0);
+    }
+}
@@ -0,0 +1,113 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+use wayland_client::{backend::WaylandError, ConnectError, DispatchError};
+
+/// Everything that can go wrong talking to the workspace manager. Each variant has its own process
+/// exit code, so scripts can tell the failures apart:
+///
+/// | code | variant               |
+/// |------|-----------------------|
+/// | 1    | `Connection`          |
+/// | 2    | `InvalidArgument`     |
+/// | 3    | `NoCompositor`        |
+/// | 4    | `ProtocolUnsupported` |
+/// | 5    | `NotFound`            |
+/// | 6    | `Ambiguous`           |
+/// | 7    | `CapabilityMissing`   |
+/// | 8    | `Rejected`            |
+/// | 9    | `Finished`            |
+/// | 10   | `Io`                  |
+/// | 11   | `Timeout`             |
+///
+/// Code 2 is shared with command line usage errors reported by clap.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "message", rename_all = "snake_case")]
+pub enum Error {
+    /// The wayland connection failed after it was established.
+    Connection(String),
+    /// Arguments that can not describe a valid selection, e.g. coordinates with the wrong number of axes.
+    InvalidArgument(String),
+    /// No wayland compositor could be reached.
+    NoCompositor(String),
+    /// The compositor does not advertise the workspace protocol, or the bound version lacks a request.
+    ProtocolUnsupported(String),
+    /// No workspace or output matches the selection.
+    NotFound(String),
+    /// More than one workspace matches a selection that has to be unique.
+    Ambiguous(String),
+    /// The workspace or group does not advertise the capability for the request.
+    CapabilityMissing(String),
+    /// The compositor refused the requests with a protocol error or did not apply them.
+    Rejected(String),
+    /// The compositor finished the workspace manager, no further requests are possible.
+    Finished,
+    /// A file could not be written, e.g. for missing permissions or a full disk.
+    Io(String),
+    /// The awaited condition did not hold within the timeout.
+    Timeout(String),
+}
+
+impl Error {
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Error::Connection(_) => 1,
+            Error::InvalidArgument(_) => 2,
+            Error::NoCompositor(_) => 3,
+            Error::ProtocolUnsupported(_) => 4,
+            Error::NotFound(_) => 5,
+            Error::Ambiguous(_) => 6,
+            Error::CapabilityMissing(_) => 7,
+            Error::Rejected(_) => 8,
+            Error::Finished => 9,
+            Error::Io(_) => 10,
+            Error::Timeout(_) => 11,
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Connection(msg)
+            | Error::InvalidArgument(msg)
+            | Error::NoCompositor(msg)
+            | Error::ProtocolUnsupported(msg)
+            | Error::NotFound(msg)
+            | Error::Ambiguous(msg)
+            | Error::CapabilityMissing(msg)
+            | Error::Rejected(msg)
+            | Error::Io(msg)
+            | Error::Timeout(msg) => write!(f, "{msg}"),
+            Error::Finished => write!(
+                f,
+                "Compositor finished the workspace manager, no further workspace requests are possible"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<ConnectError> for Error {
+    fn from(e: ConnectError) -> Self {
+        Error::NoCompositor(format!("Unable to connect to wayland compositor: {e}"))
+    }
+}
+
+impl From<DispatchError> for Error {
+    fn from(e: DispatchError) -> Self {
+        match e {
+            DispatchError::Backend(WaylandError::Protocol(e)) => {
+                Error::Rejected(format!("Compositor sent protocol error: {e}"))
+            }
+            e => Error::Connection(format!("Wayland connection failed: {e}")),
+        }
+    }
+}
+
+impl From<WaylandError> for Error {
+    fn from(e: WaylandError) -> Self {
+        DispatchError::Backend(e).into()
+    }
+}
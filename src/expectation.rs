@@ -0,0 +1,98 @@
+use std::fmt::Display;
+
+use crate::workspace_backend::{GroupHandle, WorkspaceHandle};
+use crate::workspace_state::{TilingState, Workspace, WorkspaceState, WorkspaceStates};
+
+/// Outcome of a request, checked against the state the compositor sends after the commit.
+#[derive(Debug, Clone)]
+pub enum Expectation {
+    Active(WorkspaceHandle),
+    Inactive(WorkspaceHandle),
+    Removed(WorkspaceHandle),
+    Assigned(WorkspaceHandle, GroupHandle),
+    /// A workspace with the name appears in the group. `existing` counts the workspaces that
+    /// already had the name before the request.
+    Created {
+        group: GroupHandle,
+        name: String,
+        existing: usize,
+    },
+    Renamed(WorkspaceHandle, String),
+    TilingState(WorkspaceHandle, TilingState),
+}
+
+impl Expectation {
+    pub fn created(state: &WorkspaceState, group: &GroupHandle, name: &str) -> Self {
+        Expectation::Created {
+            group: group.clone(),
+            name: name.to_string(),
+            existing: named_in_group(state, group, name),
+        }
+    }
+
+    /// Returns a description of the mismatch if the state does not show the expected outcome.
+    pub fn check(&self, state: &WorkspaceState) -> Result<(), String> {
+        let find = |handle: &WorkspaceHandle| state.workspaces.iter().find(|ws| &ws.handle == handle);
+        let mismatch = match self {
+            Expectation::Removed(handle) => find(handle).map(|ws| format!("{} was not removed", Label(ws))),
+            Expectation::Created {
+                group,
+                name,
+                existing,
+            } => (named_in_group(state, group, name) <= *existing)
+                .then(|| format!("no workspace \"{name}\" was created")),
+            Expectation::Active(handle)
+            | Expectation::Inactive(handle)
+            | Expectation::Assigned(handle, _)
+            | Expectation::Renamed(handle, _)
+            | Expectation::TilingState(handle, _) => match find(handle) {
+                None => Some("workspace was removed".to_string()),
+                Some(ws) => self.check_workspace(ws),
+            },
+        };
+        match mismatch {
+            Some(mismatch) => Err(mismatch),
+            None => Ok(()),
+        }
+    }
+
+    fn check_workspace(&self, ws: &Workspace) -> Option<String> {
+        match self {
+            Expectation::Active(_) if !ws.state.contains(WorkspaceStates::Active) => {
+                Some(format!("{} is not active", Label(ws)))
+            }
+            Expectation::Inactive(_) if ws.state.contains(WorkspaceStates::Active) => {
+                Some(format!("{} is still active", Label(ws)))
+            }
+            Expectation::Assigned(_, group) if ws.group.as_ref() != Some(group) => {
+                Some(format!("{} was not assigned to the group", Label(ws)))
+            }
+            Expectation::Renamed(_, name) if ws.name.as_ref() != Some(name) => {
+                Some(format!("{} was not renamed to \"{name}\"", Label(ws)))
+            }
+            Expectation::TilingState(_, tiling_state) if ws.tiling_state != Some(*tiling_state) => {
+                Some(format!("{} has tiling state {:?}", Label(ws), ws.tiling_state))
+            }
+            _ => None,
+        }
+    }
+}
+
+fn named_in_group(state: &WorkspaceState, group: &GroupHandle, name: &str) -> usize {
+    state
+        .workspaces
+        .iter()
+        .filter(|ws| ws.group.as_ref() == Some(group) && ws.name.as_deref() == Some(name))
+        .count()
+}
+
+struct Label<'a>(&'a Workspace);
+
+impl Display for Label<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.0.name {
+            Some(name) => write!(f, "workspace \"{name}\""),
+            None => write!(f, "workspace {}", self.0.id()),
+        }
+    }
+}
@@ -1,10 +1,12 @@
+//! Client side bindings generated from the protocol XMLs in `resources/`.
 #![warn(missing_docs)]
 #![forbid(improper_ctypes, unsafe_op_in_unsafe_fn)]
 #![cfg_attr(docsrs, feature(doc_auto_cfg))]
 #![cfg_attr(rustfmt, rustfmt_skip)]
 
+/// Workspace management protocols, one module per supported protocol version.
 pub mod workspace {
-    #[allow(non_upper_case_globals, non_camel_case_types)]
+    #[allow(non_upper_case_globals, non_camel_case_types, missing_docs)]
     pub mod ext_v0 {
         pub mod client {
             use wayland_client;
@@ -26,7 +28,7 @@ pub mod workspace {
             wayland_scanner::generate_client_code!("./resources/ext-workspace-unstable-v1.xml");
         }
     }
-    #[allow(non_upper_case_globals, non_camel_case_types)]
+    #[allow(non_upper_case_globals, non_camel_case_types, missing_docs)]
     pub mod ext_v1 {
         pub mod client {
             use wayland_client;
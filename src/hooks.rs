@@ -0,0 +1,357 @@
+use std::collections::VecDeque;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command};
+use std::time::{Duration, Instant};
+
+use log::{debug, info, warn};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::error::Error;
+use crate::watch::{self, Change};
+use crate::workspace_state::Batch;
+use crate::workspace_manager::WorkspaceClient;
+
+/// Configuration file, by default `$XDG_CONFIG_HOME/wsctrl/config.toml`:
+///
+/// ```toml
+/// [hooks]
+/// on_urgent = "notify-send \"$WSCTRL_WORKSPACE_NAME needs attention\""
+/// debounce_ms = 200
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(default)]
+    pub hooks: Hooks,
+}
+
+/// Shell commands run on workspace and output changes. Commands get the change as environment
+/// variables named after the fields of the `watch` output, e.g. `WSCTRL_EVENT`,
+/// `WSCTRL_WORKSPACE_NAME`, `WSCTRL_WORKSPACE_OUTPUT` or `WSCTRL_FROM`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct Hooks {
+    pub on_create: Option<String>,
+    pub on_remove: Option<String>,
+    pub on_activate: Option<String>,
+    pub on_deactivate: Option<String>,
+    pub on_urgent: Option<String>,
+    pub on_urgent_cleared: Option<String>,
+    pub on_rename: Option<String>,
+    pub on_move: Option<String>,
+    pub on_output_enter: Option<String>,
+    pub on_output_leave: Option<String>,
+    /// A hook only runs once the same change of the same workspace or output did not happen again
+    /// for this long, with the latest change.
+    pub debounce_ms: u64,
+    /// Hooks beyond this many running commands wait until one finished.
+    pub max_concurrent: usize,
+}
+
+impl Default for Hooks {
+    fn default() -> Self {
+        Hooks {
+            on_create: None,
+            on_remove: None,
+            on_activate: None,
+            on_deactivate: None,
+            on_urgent: None,
+            on_urgent_cleared: None,
+            on_rename: None,
+            on_move: None,
+            on_output_enter: None,
+            on_output_leave: None,
+            debounce_ms: 0,
+            max_concurrent: 4,
+        }
+    }
+}
+
+impl Config {
+    /// `$XDG_CONFIG_HOME/wsctrl/config.toml`, or `~/.config/wsctrl/config.toml`.
+    pub fn default_path() -> Result<PathBuf, Error> {
+        let dir = env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+            .ok_or(Error::InvalidArgument(
+                "Neither XDG_CONFIG_HOME nor HOME is set".to_string(),
+            ))?;
+        Ok(dir.join("wsctrl").join("config.toml"))
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, Error> {
+        let content = fs::read_to_string(path).map_err(|e| {
+            Error::InvalidArgument(format!("Unable to read config file {}: {e}", path.display()))
+        })?;
+        let config: Config = toml::from_str(&content).map_err(|e| {
+            Error::InvalidArgument(format!("Invalid config file {}: {e}", path.display()))
+        })?;
+        if config.hooks.max_concurrent == 0 {
+            return Err(Error::InvalidArgument(format!(
+                "Invalid config file {}: max_concurrent must be at least 1",
+                path.display()
+            )));
+        }
+        Ok(config)
+    }
+}
+
+impl Hooks {
+    fn command(&self, change: &Change) -> Option<&String> {
+        match change {
+            Change::WorkspaceCreated { .. } => self.on_create.as_ref(),
+            Change::WorkspaceRemoved { .. } => self.on_remove.as_ref(),
+            Change::Activated { .. } => self.on_activate.as_ref(),
+            Change::Deactivated { .. } => self.on_deactivate.as_ref(),
+            Change::Urgent { .. } => self.on_urgent.as_ref(),
+            Change::UrgentCleared { .. } => self.on_urgent_cleared.as_ref(),
+            Change::Renamed { .. } => self.on_rename.as_ref(),
+            Change::Moved { .. } => self.on_move.as_ref(),
+            Change::OutputEnter { .. } => self.on_output_enter.as_ref(),
+            Change::OutputLeave { .. } => self.on_output_leave.as_ref(),
+        }
+    }
+}
+
+/// Dispatches until the compositor finishes the workspace manager or `handle` returns false, and
+/// runs the hooks for the changes of every batch. `handle` gets each batch with its changes, e.g.
+/// to print them. Hooks still debounced at the end run right away, running ones are waited for.
+pub fn run(
+    client: &mut WorkspaceClient,
+    hooks: Hooks,
+    mut handle: impl FnMut(Batch, &[Change]) -> bool,
+) -> Result<(), Error> {
+    let mut runner = Runner::new(hooks);
+    client.state_mut().batches = Some(Vec::new());
+    'dispatch: while !client.is_finished() {
+        match runner.next_wakeup(Instant::now()) {
+            Some(timeout) => {
+                client.dispatch_timeout(timeout)?;
+            }
+            None => client.blocking_dispatch()?,
+        }
+        let batches = client.state_mut().batches.replace(Vec::new()).unwrap_or_default();
+        let now = Instant::now();
+        for batch in batches {
+            let changes = watch::changes(&batch);
+            for change in &changes {
+                runner.push(change.clone(), now);
+            }
+            if !handle(batch, &changes) {
+                break 'dispatch;
+            }
+        }
+        runner.run_due(Instant::now());
+    }
+    if client.is_finished() {
+        info!("compositor finished the workspace manager, stop watching");
+    }
+    runner.finish();
+    Ok(())
+}
+
+// changes of the same kind and subject are debounced together
+type Key = (&'static str, u32);
+
+struct Runner {
+    hooks: Hooks,
+    debounced: Vec<(Key, Change, Instant)>,
+    queued: VecDeque<Command>,
+    running: Vec<Child>,
+}
+
+// how often to check for finished commands while hooks wait for a free slot
+const REAP_INTERVAL: Duration = Duration::from_millis(50);
+
+impl Runner {
+    fn new(hooks: Hooks) -> Self {
+        Runner {
+            hooks,
+            debounced: Vec::new(),
+            queued: VecDeque::new(),
+            running: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, change: Change, now: Instant) {
+        if self.hooks.command(&change).is_none() {
+            return;
+        }
+        let key = key(&change);
+        let deadline = now + Duration::from_millis(self.hooks.debounce_ms);
+        self.debounced.retain(|(k, _, _)| *k != key);
+        self.debounced.push((key, change, deadline));
+    }
+
+    fn next_wakeup(&self, now: Instant) -> Option<Duration> {
+        let debounced = self
+            .debounced
+            .iter()
+            .map(|(_, _, deadline)| deadline.saturating_duration_since(now))
+            .min();
+        let waiting = (!self.queued.is_empty()).then_some(REAP_INTERVAL);
+        debounced.into_iter().chain(waiting).min()
+    }
+
+    fn run_due(&mut self, now: Instant) {
+        let (due, debounced) = std::mem::take(&mut self.debounced)
+            .into_iter()
+            .partition::<Vec<_>, _>(|(_, _, deadline)| *deadline <= now);
+        self.debounced = debounced;
+        for (_, change, _) in due {
+            self.queue(&change);
+        }
+        self.start_queued();
+    }
+
+    fn finish(mut self) {
+        for (_, change, _) in std::mem::take(&mut self.debounced) {
+            self.queue(&change);
+        }
+        while !self.queued.is_empty() {
+            self.start_queued();
+            std::thread::sleep(REAP_INTERVAL);
+        }
+        for mut child in self.running {
+            if let Err(e) = child.wait() {
+                warn!("unable to wait for hook: {e}");
+            }
+        }
+    }
+
+    fn queue(&mut self, change: &Change) {
+        let Some(script) = self.hooks.command(change) else {
+            return;
+        };
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(script).envs(environment(change));
+        self.queued.push_back(command);
+    }
+
+    fn start_queued(&mut self) {
+        self.running.retain_mut(|child| !matches!(child.try_wait(), Ok(Some(_))));
+        while self.running.len() < self.hooks.max_concurrent {
+            let Some(mut command) = self.queued.pop_front() else {
+                break;
+            };
+            debug!("running hook {command:?}");
+            match command.spawn() {
+                Ok(child) => self.running.push(child),
+                Err(e) => warn!("unable to run hook: {e}"),
+            }
+        }
+    }
+}
+
+fn key(change: &Change) -> Key {
+    match change {
+        Change::WorkspaceCreated { workspace } => ("create", workspace.protocol_id),
+        Change::WorkspaceRemoved { workspace } => ("remove", workspace.protocol_id),
+        Change::Activated { workspace } => ("activate", workspace.protocol_id),
+        Change::Deactivated { workspace } => ("deactivate", workspace.protocol_id),
+        Change::Urgent { workspace } => ("urgent", workspace.protocol_id),
+        Change::UrgentCleared { workspace } => ("urgent_cleared", workspace.protocol_id),
+        Change::Renamed { workspace, .. } => ("rename", workspace.protocol_id),
+        Change::Moved { workspace, .. } => ("move", workspace.protocol_id),
+        Change::OutputEnter { group, .. } => ("output_enter", *group),
+        Change::OutputLeave { group, .. } => ("output_leave", *group),
+    }
+}
+
+/// The change as environment variables, named like its json fields with a `WSCTRL_` prefix.
+/// Fields without value are left out.
+pub fn environment(change: &Change) -> Vec<(String, String)> {
+    let mut variables = Vec::new();
+    if let Ok(value) = serde_json::to_value(change) {
+        flatten("WSCTRL", &value, &mut variables);
+    }
+    variables
+}
+
+fn flatten(prefix: &str, value: &Value, variables: &mut Vec<(String, String)>) {
+    match value {
+        Value::Object(fields) => {
+            for (field, value) in fields {
+                flatten(&format!("{prefix}_{}", screaming_snake(field)), value, variables);
+            }
+        }
+        Value::Null => {}
+        Value::String(s) => variables.push((prefix.to_string(), s.clone())),
+        other => variables.push((prefix.to_string(), other.to_string())),
+    }
+}
+
+// protocolId -> PROTOCOL_ID
+fn screaming_snake(field: &str) -> String {
+    let mut name = String::new();
+    for c in field.chars() {
+        if c.is_ascii_uppercase() {
+            name.push('_');
+        }
+        name.push(c.to_ascii_uppercase());
+    }
+    name
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::watch::WorkspaceRef;
+
+    fn activated(protocol_id: u32, name: &str) -> Change {
+        Change::Activated {
+            workspace: WorkspaceRef {
+                name: Some(name.to_string()),
+                id: None,
+                protocol_id,
+                output: Some("DP-4".to_string()),
+            },
+        }
+    }
+
+    #[test]
+    fn environment_of_change() {
+        let mut variables = environment(&activated(7, "mail"));
+        variables.sort();
+        assert_eq!(
+            variables,
+            [
+                ("WSCTRL_EVENT", "activated"),
+                ("WSCTRL_WORKSPACE_NAME", "mail"),
+                ("WSCTRL_WORKSPACE_OUTPUT", "DP-4"),
+                ("WSCTRL_WORKSPACE_PROTOCOL_ID", "7"),
+            ]
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+        );
+    }
+
+    #[test]
+    fn debounce_keeps_latest_change_per_workspace() {
+        let mut runner = Runner::new(Hooks {
+            on_activate: Some("true".to_string()),
+            debounce_ms: 100,
+            ..Hooks::default()
+        });
+        let start = Instant::now();
+        runner.push(activated(1, "web"), start);
+        runner.push(activated(2, "mail"), start);
+        runner.push(activated(1, "www"), start + Duration::from_millis(50));
+        assert_eq!(runner.next_wakeup(start), Some(Duration::from_millis(100)));
+
+        runner.run_due(start + Duration::from_millis(100));
+        assert_eq!(runner.debounced.len(), 1);
+        assert_eq!(runner.debounced[0].1, activated(1, "www"));
+        runner.finish();
+    }
+
+    #[test]
+    fn changes_without_hook_are_ignored() {
+        let mut runner = Runner::new(Hooks::default());
+        runner.push(activated(1, "web"), Instant::now());
+        assert!(runner.debounced.is_empty());
+        assert_eq!(runner.next_wakeup(Instant::now()), None);
+    }
+}
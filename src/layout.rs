@@ -0,0 +1,344 @@
+use std::fmt::Display;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::Error;
+use crate::expectation::Expectation;
+use crate::workspace_backend::{GroupHandle, WorkspaceHandle};
+use crate::workspace_manager::WorkspaceClient;
+use crate::workspace_state::{
+    output_name, Workspace, WorkspaceCapabilities, WorkspaceState, WorkspaceStates,
+};
+
+/// Desired workspaces per output, read from a layout file:
+///
+/// ```toml
+/// [[output]]
+/// name = "DP-4"
+/// workspaces = ["web", "mail", "terminal"]
+/// active = "web"
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Layout {
+    #[serde(rename = "output", default)]
+    pub outputs: Vec<OutputLayout>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OutputLayout {
+    pub name: String,
+    /// Missing workspaces are created in this order. None of the protocols can reorder existing
+    /// workspaces, so their order is left to the compositor.
+    #[serde(default)]
+    pub workspaces: Vec<String>,
+    /// One of `workspaces`.
+    pub active: Option<String>,
+}
+
+/// Single request of a plan, with the workspace and output names for printing.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Step {
+    Assign {
+        workspace: String,
+        from: Option<String>,
+        output: String,
+        #[serde(skip)]
+        handle: WorkspaceHandle,
+        #[serde(skip)]
+        group: GroupHandle,
+    },
+    Create {
+        workspace: String,
+        output: String,
+        #[serde(skip)]
+        group: GroupHandle,
+    },
+    Remove {
+        workspace: String,
+        output: String,
+        #[serde(skip)]
+        handle: WorkspaceHandle,
+    },
+    /// `handle` is `None` for workspaces created by the same plan.
+    Activate {
+        workspace: String,
+        output: String,
+        #[serde(skip)]
+        handle: Option<WorkspaceHandle>,
+        #[serde(skip)]
+        group: GroupHandle,
+    },
+}
+
+impl Layout {
+    /// Reads JSON from files with a `.json` extension and TOML from all others.
+    pub fn from_file(path: &Path) -> Result<Self, Error> {
+        let content = fs::read_to_string(path).map_err(|e| {
+            Error::InvalidArgument(format!("Unable to read layout file {}: {e}", path.display()))
+        })?;
+        let layout: Layout = if path.extension().is_some_and(|ext| ext == "json") {
+            serde_json::from_str(&content).map_err(|e| e.to_string())
+        } else {
+            toml::from_str(&content).map_err(|e| e.to_string())
+        }
+        .map_err(|e| Error::InvalidArgument(format!("Invalid layout file {}: {e}", path.display())))?;
+        layout.validate()?;
+        Ok(layout)
+    }
+
+    fn validate(&self) -> Result<(), Error> {
+        for (i, output) in self.outputs.iter().enumerate() {
+            if self.outputs[..i].iter().any(|o| o.name == output.name) {
+                return Err(Error::InvalidArgument(format!(
+                    "Output {} is listed more than once",
+                    output.name
+                )));
+            }
+            for (j, name) in output.workspaces.iter().enumerate() {
+                if output.workspaces[..j].contains(name) {
+                    return Err(Error::InvalidArgument(format!(
+                        "Workspace {name} is listed more than once on output {}",
+                        output.name
+                    )));
+                }
+            }
+            if let Some(active) = &output.active {
+                if !output.workspaces.contains(active) {
+                    return Err(Error::InvalidArgument(format!(
+                        "Active workspace {active} is not listed on output {}",
+                        output.name
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Steps that turn `state` into the layout: assignments, creations, removals and activations,
+    /// in that order. A listed workspace is taken from its output if it is already there, otherwise
+    /// a workspace with the name is assigned from elsewhere if it advertises the assign capability,
+    /// otherwise it is created. Workspaces on the listed outputs that are not in the layout are only
+    /// removed with `remove_extra`.
+    pub fn plan(&self, state: &WorkspaceState, remove_extra: bool) -> Result<Vec<Step>, Error> {
+        let groups = self
+            .outputs
+            .iter()
+            .map(|output| {
+                state
+                    .groups
+                    .iter()
+                    .find(|group| group.get_output_name().as_ref() == Some(&output.name))
+                    .map(|group| &group.handle)
+                    .ok_or(Error::NotFound(format!(
+                        "Unable to find output with name {}!",
+                        output.name
+                    )))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // workspaces already in place are claimed first, so they are not moved to another output
+        let mut claimed = Vec::<&WorkspaceHandle>::new();
+        let placed = self
+            .outputs
+            .iter()
+            .zip(&groups)
+            .map(|(output, group)| {
+                output
+                    .workspaces
+                    .iter()
+                    .map(|name| {
+                        let workspace = state.workspaces.iter().find(|ws| {
+                            ws.group.as_ref() == Some(*group)
+                                && ws.name.as_ref() == Some(name)
+                                && !claimed.contains(&&ws.handle)
+                        });
+                        if let Some(workspace) = workspace {
+                            claimed.push(&workspace.handle);
+                        }
+                        workspace
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        let (mut changes, mut activations) = (Vec::new(), Vec::new());
+        for ((output, group), placed) in self.outputs.iter().zip(&groups).zip(placed) {
+            for (name, workspace) in output.workspaces.iter().zip(placed) {
+                let workspace = workspace.or_else(|| {
+                    let moved = state.workspaces.iter().find(|ws| {
+                        ws.capabilities.contains(WorkspaceCapabilities::Assign)
+                            && ws.name.as_ref() == Some(name)
+                            && !claimed.contains(&&ws.handle)
+                    });
+                    match moved {
+                        Some(ws) => {
+                            claimed.push(&ws.handle);
+                            changes.push(Step::Assign {
+                                workspace: name.clone(),
+                                from: ws.group.as_ref().and_then(|g| output_name(&state.groups, g)),
+                                output: output.name.clone(),
+                                handle: ws.handle.clone(),
+                                group: (*group).clone(),
+                            });
+                        }
+                        None => changes.push(Step::Create {
+                            workspace: name.clone(),
+                            output: output.name.clone(),
+                            group: (*group).clone(),
+                        }),
+                    }
+                    moved
+                });
+                let active = workspace.is_some_and(|ws| ws.state.contains(WorkspaceStates::Active));
+                if output.active.as_ref() == Some(name) && !active {
+                    activations.push(Step::Activate {
+                        workspace: name.clone(),
+                        output: output.name.clone(),
+                        handle: workspace.map(|ws| ws.handle.clone()),
+                        group: (*group).clone(),
+                    });
+                }
+            }
+        }
+
+        // sorted by kind, keeping the layout order inside each kind
+        changes.sort_by_key(|step| matches!(step, Step::Create { .. }));
+        if remove_extra {
+            for (output, group) in self.outputs.iter().zip(&groups) {
+                changes.extend(
+                    state
+                        .workspaces
+                        .iter()
+                        .filter(|ws| ws.group.as_ref() == Some(*group) && !claimed.contains(&&ws.handle))
+                        .map(|ws| Step::Remove {
+                            workspace: label(ws),
+                            output: output.name.clone(),
+                            handle: ws.handle.clone(),
+                        }),
+                );
+            }
+        }
+        changes.extend(activations);
+        Ok(changes)
+    }
+}
+
+/// Sends the plan. Workspaces created by the plan are only known after a commit of their own, so
+/// activating them needs a second commit. With `wait` both commits wait until the compositor
+/// applied the steps.
+pub fn apply(
+    client: &mut WorkspaceClient,
+    plan: &[Step],
+    wait: bool,
+    timeout: Duration,
+) -> Result<(), Error> {
+    if plan.is_empty() {
+        return Ok(());
+    }
+    let known = client
+        .state()
+        .workspaces
+        .iter()
+        .map(|ws| ws.handle.clone())
+        .collect::<Vec<_>>();
+    let mut expectations = Vec::new();
+    let mut created = Vec::new();
+    for step in plan {
+        match step {
+            Step::Assign { handle, group, .. } => {
+                handle.assign(group)?;
+                expectations.push(Expectation::Assigned(handle.clone(), group.clone()));
+            }
+            Step::Create {
+                workspace, group, ..
+            } => {
+                expectations.push(Expectation::created(client.state(), group, workspace));
+                group.create_workspace(workspace.clone());
+            }
+            Step::Remove { handle, .. } => {
+                handle.remove();
+                expectations.push(Expectation::Removed(handle.clone()));
+            }
+            Step::Activate {
+                handle: Some(handle),
+                ..
+            } => {
+                handle.activate();
+                expectations.push(Expectation::Active(handle.clone()));
+            }
+            Step::Activate {
+                workspace,
+                handle: None,
+                group,
+                ..
+            } => created.push((workspace, group)),
+        }
+    }
+    if wait || !created.is_empty() {
+        client.commit_and_wait(&expectations, timeout)?;
+    } else {
+        client.commit()?;
+    }
+    if created.is_empty() {
+        return Ok(());
+    }
+
+    let mut expectations = Vec::new();
+    for (name, group) in created {
+        let workspace = client
+            .state()
+            .workspaces
+            .iter()
+            .find(|ws| {
+                ws.group.as_ref() == Some(group)
+                    && ws.name.as_ref() == Some(name)
+                    && !known.contains(&ws.handle)
+            })
+            .ok_or(Error::Rejected(format!("Created workspace {name} not found")))?;
+        workspace.activate();
+        expectations.push(Expectation::Active(workspace.handle.clone()));
+    }
+    if wait {
+        client.commit_and_wait(&expectations, timeout)
+    } else {
+        client.commit()
+    }
+}
+
+fn label(workspace: &Workspace) -> String {
+    match &workspace.name {
+        Some(name) => name.clone(),
+        None => workspace.id().to_string(),
+    }
+}
+
+impl Display for Step {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Step::Assign {
+                workspace,
+                from: Some(from),
+                output,
+                ..
+            } => write!(f, "assign \"{workspace}\" from {from} to {output}"),
+            Step::Assign {
+                workspace, output, ..
+            } => write!(f, "assign \"{workspace}\" to {output}"),
+            Step::Create {
+                workspace, output, ..
+            } => write!(f, "create \"{workspace}\" on {output}"),
+            Step::Remove {
+                workspace, output, ..
+            } => write!(f, "remove \"{workspace}\" from {output}"),
+            Step::Activate {
+                workspace, output, ..
+            } => write!(f, "activate \"{workspace}\" on {output}"),
+        }
+    }
+}
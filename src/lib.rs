@@ -0,0 +1,22 @@
+// based on the list output example from the smithay client toolkit
+#[macro_use]
+mod protocol_macro;
+pub mod daemon;
+mod error;
+pub mod expectation;
+pub mod ext;
+pub mod hooks;
+pub mod layout;
+pub mod snapshot;
+pub mod transition;
+pub mod watch;
+mod workspace_manager;
+pub mod workspace_backend;
+pub mod workspace_state;
+mod workspace_protocol_ext_v0;
+mod workspace_protocol_ext_v1;
+mod workspace_protocol_cosmic_v1;
+pub mod cli;
+
+pub use error::Error;
+pub use workspace_manager::{WorkspaceClient, WorkspaceManager};
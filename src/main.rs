@@ -1,24 +1,523 @@
-// based on the list output example from the smithay client toolkit
-#[macro_use]
-pub(crate) mod protocol_macro;
-pub(crate) mod ext;
-mod workspace_manager;
-pub(crate) mod workspace_state;
-pub(crate) mod workspace_protocol_ext_v0;
-mod workspace_protocol_ext_v1;
-mod workspace_protocol_cosmic_v1;
-pub(crate) mod cli;
-
 use clap::Parser;
-use cli::Cli;
-use workspace_manager::WorkspaceManager;
-use std::error::Error;
+use serde::Serialize;
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::iter;
+use std::path::Path;
+use std::process::ExitCode;
+use std::time::Duration;
+use wsctrl::cli::{BatchLine, Cli, Commands, ListArgs, OutputSelector, WaitArgs, WorkspaceSelector};
+use wsctrl::daemon;
+use wsctrl::expectation::Expectation;
+use wsctrl::hooks::{self, Config, Hooks};
+use wsctrl::layout::{self, Layout, Step};
+use wsctrl::snapshot::Snapshot;
+use wsctrl::workspace_state::{
+    Checkpoint, GroupCapabilities, Workspace, WorkspaceOrder, WorkspaceStates,
+};
+use wsctrl::workspace_backend::GroupHandle;
+use wsctrl::{Error, WorkspaceClient};
 
-fn main() -> Result<(), Box<dyn Error>> {
+fn main() -> ExitCode {
     env_logger::init();
 
     let args = Cli::parse();
-    WorkspaceManager::exec(&args)?;
+    let forwarded = match args.global_opts.no_daemon {
+        true => None,
+        false => daemon::forward(&env::args().skip(1).collect::<Vec<_>>()),
+    };
+    match forwarded.unwrap_or_else(|| exec(&args)) {
+        Ok(output) => {
+            print!("{output}");
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            if args.global_opts.json {
+                eprintln!("{}", serde_json::json!({ "error": e, "exitCode": e.exit_code() }));
+            } else {
+                eprintln!("Error: {e}");
+            }
+            ExitCode::from(e.exit_code())
+        }
+    }
+}
+
+fn exec(args: &Cli) -> Result<String, Error> {
+    let mut client = WorkspaceClient::connect(args.global_opts.protocol)?;
+    run(&mut client, args)
+}
+
+/// Runs the command with the connected client and returns what it prints to stdout.
+fn run(client: &mut WorkspaceClient, args: &Cli) -> Result<String, Error> {
+    client.state_mut().order = args.global_opts.order;
+    let wait_timeout = Duration::from_millis(args.global_opts.wait_timeout);
+    let expectations = match &args.command {
+        Commands::List(list_args) => return list_data(client, list_args, args.global_opts.json),
+        Commands::Watch { full, config } => {
+            let hooks = match config {
+                Some(path) => Config::from_file(path)?.hooks,
+                None => Hooks::default(),
+            };
+            watch(client, *full, hooks)?;
+            return Ok(String::new());
+        }
+        Commands::Wait(wait_args) => {
+            wait(client, wait_args)?;
+            return Ok(String::new());
+        }
+        Commands::Hooks { config } => {
+            let path = match config {
+                Some(path) => path.clone(),
+                None => Config::default_path()?,
+            };
+            hooks::run(client, Config::from_file(&path)?.hooks, |_, _| true)?;
+            return Ok(String::new());
+        }
+        Commands::Daemon => {
+            daemon::serve(client, serve_request)?;
+            return Ok(String::new());
+        }
+        Commands::Apply {
+            file,
+            dry_run,
+            remove_extra,
+        } => {
+            let plan = Layout::from_file(file)?.plan(client.state(), *remove_extra)?;
+            if *dry_run {
+                return Ok(format_plan(&plan, args.global_opts.json));
+            }
+            layout::apply(client, &plan, args.global_opts.wait, wait_timeout)?;
+            return Ok(String::new());
+        }
+        Commands::Save { file } => {
+            let snapshot = serde_json::to_string_pretty(&Snapshot::capture(client.state()))
+                .expect("snapshot is plain data");
+            fs::write(file, snapshot + "\n").map_err(|e| {
+                Error::Io(format!("Unable to write snapshot {}: {e}", file.display()))
+            })?;
+            return Ok(String::new());
+        }
+        Commands::Restore { file, dry_run } => {
+            let snapshot: Snapshot = fs::read_to_string(file)
+                .map_err(|e| e.to_string())
+                .and_then(|content| serde_json::from_str(&content).map_err(|e| e.to_string()))
+                .map_err(|e| {
+                    Error::InvalidArgument(format!("Unable to read snapshot {}: {e}", file.display()))
+                })?;
+            let plan = snapshot.plan(client.state());
+            if *dry_run {
+                return Ok(format_plan(&plan, args.global_opts.json));
+            }
+            layout::apply(client, &plan, args.global_opts.wait, wait_timeout)?;
+            return Ok(String::new());
+        }
+        Commands::CreateWorkspace {
+            workspace_name,
+            output,
+            print_id: true,
+        } => {
+            client.require_ids()?;
+            let group = client.group(output)?.handle.clone();
+            let workspace = create_and_find(client, &group, workspace_name, wait_timeout)?;
+            let id = workspace.id.clone().ok_or(Error::Rejected(format!(
+                "Compositor sent no id for workspace {workspace_name}"
+            )))?;
+            return Ok(format!("{id}\n"));
+        }
+        // every line is resolved against the state of the initial roundtrip, the compositor only
+        // applies the requests with the single commit below
+        Commands::Batch { file } => read_batch(file.as_deref())?
+            .iter()
+            .map(|command| request(client, command, wait_timeout))
+            .collect::<Result<Vec<_>, _>>()?
+            .concat(),
+        command => request(client, command, wait_timeout)?,
+    };
+    if args.global_opts.wait {
+        client.commit_and_wait(&expectations, wait_timeout)?;
+    } else {
+        client.commit()?;
+    }
+    Ok(String::new())
+}
+
+// files and stdin belong to the client, so only commands working on the workspace state alone
+// are served
+fn serve_request(client: &mut WorkspaceClient, args: Vec<String>) -> daemon::Response {
+    let Ok(args) = Cli::try_parse_from(iter::once("wsctrl".to_string()).chain(args)) else {
+        return daemon::Response::Unsupported;
+    };
+    let served = matches!(
+        args.command,
+        Commands::Activate(_)
+            | Commands::Deactivate(_)
+            | Commands::Assign { .. }
+            | Commands::Remove(_)
+            | Commands::CreateWorkspace { .. }
+            | Commands::Rename { .. }
+            | Commands::SetTilingState { .. }
+            | Commands::Next(_)
+            | Commands::Prev(_)
+            | Commands::MoveFocus { .. }
+            | Commands::List(_)
+    );
+    if !served || args.global_opts.protocol.is_some_and(|p| p != client.state().protocol) {
+        return daemon::Response::Unsupported;
+    }
+    run(client, &args).into()
+}
+
+/// Dispatches until the condition holds for any of the selected workspaces, or for none of them
+/// with `--removed`.
+fn wait(client: &mut WorkspaceClient, args: &WaitArgs) -> Result<(), Error> {
+    let selector = args.workspace.clone().map(|matching| WorkspaceSelector {
+        matching,
+        ..Default::default()
+    });
+    let condition = &args.condition;
+    let matches = |client: &WorkspaceClient| -> Result<bool, Error> {
+        let workspaces = client.matching_workspaces(selector.as_ref(), args.output.as_ref())?;
+        let any = |state: WorkspaceStates| workspaces.iter().any(|ws| ws.state.contains(state.clone()));
+        Ok(if condition.removed {
+            workspaces.is_empty()
+        } else if condition.active {
+            any(WorkspaceStates::Active)
+        } else if condition.urgent {
+            any(WorkspaceStates::Urgent)
+        } else {
+            !workspaces.is_empty()
+        })
+    };
+    // evaluated once up front, so invalid selections fail instead of never matching
+    matches(client)?;
+    let timeout = args.timeout.map(Duration::from_millis);
+    if client.dispatch_until(timeout, |client| matches(client).unwrap_or(false))? {
+        return Ok(());
+    }
+    Err(Error::Timeout(format!(
+        "Condition did not hold within {}ms",
+        args.timeout.unwrap_or_default()
+    )))
+}
+
+/// Prints the changes of each batch as json lines until the compositor finishes the manager. With
+/// `full` the complete state is printed instead, once at the start and after each batch.
+fn watch(client: &mut WorkspaceClient, full: bool, hooks: Hooks) -> Result<(), Error> {
+    let mut stdout = io::stdout().lock();
+    let order = client.state().order;
+    if full && emit(&mut stdout, &listed(client.state().checkpoint(), order)).is_err() {
+        return Ok(());
+    }
+    hooks::run(client, hooks, |batch, changes| {
+        let written = if full {
+            emit(&mut stdout, &listed(batch.after, order))
+        } else {
+            changes.iter().try_for_each(|change| emit(&mut stdout, change))
+        };
+        // nobody is reading anymore
+        written.is_ok()
+    })
+}
+
+// in the order of `list`
+fn listed(mut checkpoint: Checkpoint, order: WorkspaceOrder) -> Checkpoint {
+    checkpoint.groups.sort_by_key(|group| group.id());
+    checkpoint.workspaces.sort_by(|a, b| order.listed(a, b));
+    checkpoint
+}
+
+fn emit(out: &mut impl Write, value: &impl Serialize) -> io::Result<()> {
+    writeln!(out, "{}", serde_json::to_string(value)?)?;
+    out.flush()
+}
+
+/// Sends the requests of a single command without committing them.
+fn request(
+    client: &mut WorkspaceClient,
+    command: &Commands,
+    wait_timeout: Duration,
+) -> Result<Vec<Expectation>, Error> {
+    let expectation = match command {
+        Commands::List(_)
+        | Commands::Watch { .. }
+        | Commands::Hooks { .. }
+        | Commands::Wait(_)
+        | Commands::Batch { .. }
+        | Commands::Apply { .. }
+        | Commands::Save { .. }
+        | Commands::Restore { .. }
+        | Commands::Daemon => {
+            return Err(Error::InvalidArgument(
+                "Command does not send workspace requests".to_string(),
+            ))
+        }
+        Commands::CreateWorkspace {
+            workspace_name,
+            output,
+            ..
+        } => {
+            let group = client.group(output)?;
+            group.create_workspace(workspace_name.to_string());
+            Expectation::created(client.state(), &group.handle, workspace_name)
+        }
+        Commands::Activate(args) => {
+            let workspaces = client.workspaces(args)?;
+            return Ok(workspaces
+                .into_iter()
+                .map(|workspace| {
+                    workspace.activate();
+                    Expectation::Active(workspace.handle.clone())
+                })
+                .collect());
+        }
+        Commands::Deactivate(args) => {
+            let workspaces = client.workspaces(args)?;
+            return Ok(workspaces
+                .into_iter()
+                .map(|workspace| {
+                    workspace.deactivate();
+                    Expectation::Inactive(workspace.handle.clone())
+                })
+                .collect());
+        }
+        Commands::Next(nav_args) | Commands::Prev(nav_args) => {
+            let forward = matches!(command, Commands::Next(_));
+            let workspace = client.relative_workspace(
+                nav_args.output.as_ref(),
+                forward,
+                nav_args.wrap(),
+                nav_args.skip_hidden,
+            )?;
+            workspace.activate();
+            Expectation::Active(workspace.handle.clone())
+        }
+        Commands::MoveFocus {
+            direction,
+            output,
+            create,
+        } => {
+            let workspace = match client.directional_workspace(output.as_ref(), *direction)? {
+                Some(workspace) => workspace,
+                None => match create {
+                    Some(name) => create_at_edge(client, output.as_ref(), name, wait_timeout)?,
+                    None => {
+                        return Err(Error::NotFound(format!(
+                            "No workspace {direction:?} of the active workspace"
+                        )))
+                    }
+                },
+            };
+            workspace.activate();
+            Expectation::Active(workspace.handle.clone())
+        }
+        Commands::Remove(args) => {
+            let workspaces = client.workspaces(args)?;
+            return Ok(workspaces
+                .into_iter()
+                .map(|workspace| {
+                    workspace.remove();
+                    Expectation::Removed(workspace.handle.clone())
+                })
+                .collect());
+        }
+        Commands::Assign {
+            workspace_args,
+            target,
+            follow,
+        } => {
+            // every target is resolved and checked before the first request is sent, like for
+            // set-tiling-state
+            let moves = client
+                .workspaces(workspace_args)?
+                .into_iter()
+                .map(|workspace| {
+                    workspace.check_assign()?;
+                    Ok((workspace, client.target_group(workspace, target)?))
+                })
+                .collect::<Result<Vec<_>, Error>>()?;
+            let mut expectations = Vec::new();
+            for (workspace, group) in moves {
+                workspace.assign(&group.handle)?;
+                expectations.push(Expectation::Assigned(workspace.handle.clone(), group.handle.clone()));
+                if *follow {
+                    workspace.activate();
+                    expectations.push(Expectation::Active(workspace.handle.clone()));
+                }
+            }
+            return Ok(expectations);
+        }
+        Commands::Rename {
+            workspace_args,
+            new_name,
+        } => {
+            if workspace_args.all {
+                return Err(Error::InvalidArgument(
+                    "Several workspaces can not be renamed to the same name".to_string(),
+                ));
+            }
+            let workspace = client.workspaces(workspace_args)?[0];
+            workspace.rename(new_name.to_string())?;
+            Expectation::Renamed(workspace.handle.clone(), new_name.to_string())
+        }
+        Commands::SetTilingState {
+            workspace_args,
+            tiling_state,
+        } => {
+            let workspaces = client.workspaces(workspace_args)?;
+            // requests sent before a failure would stay pending until the next commit, which in a
+            // daemon belongs to another command
+            for workspace in &workspaces {
+                workspace.check_set_tiling_state()?;
+            }
+            return workspaces
+                .into_iter()
+                .map(|workspace| {
+                    workspace.set_tiling_state(*tiling_state)?;
+                    Ok(Expectation::TilingState(workspace.handle.clone(), *tiling_state))
+                })
+                .collect();
+        }
+    };
+    Ok(vec![expectation])
+}
+
+/// Parses the batch from the file or stdin. Empty lines and lines starting with `#` are skipped.
+fn read_batch(file: Option<&Path>) -> Result<Vec<Commands>, Error> {
+    let input = match file {
+        Some(file) => fs::read_to_string(file).map_err(|e| {
+            Error::InvalidArgument(format!("Unable to read batch file {}: {e}", file.display()))
+        })?,
+        None => io::read_to_string(io::stdin())
+            .map_err(|e| Error::InvalidArgument(format!("Unable to read batch from stdin: {e}")))?,
+    };
+    let mut commands = Vec::new();
+    for (number, line) in input.lines().enumerate().map(|(i, line)| (i + 1, line.trim())) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let words = shlex::split(line)
+            .ok_or_else(|| Error::InvalidArgument(format!("Line {number}: unbalanced quotes")))?;
+        let command = BatchLine::try_parse_from(words)
+            .map_err(|e| {
+                let msg = e.to_string();
+                let msg = msg.lines().next().unwrap_or_default().trim_start_matches("error: ");
+                Error::InvalidArgument(format!("Line {number}: {msg}"))
+            })?
+            .command;
+        match command {
+            Commands::List(_)
+            | Commands::Watch { .. }
+            | Commands::Hooks { .. }
+            | Commands::Wait(_)
+            | Commands::Batch { .. }
+            | Commands::Apply { .. }
+            | Commands::Save { .. }
+            | Commands::Restore { .. }
+            | Commands::Daemon => {
+                return Err(Error::InvalidArgument(format!(
+                    "Line {number}: only commands that send workspace requests can be batched"
+                )))
+            }
+            // the created workspace is only known after a commit of its own
+            Commands::MoveFocus {
+                create: Some(_), ..
+            } => {
+                return Err(Error::InvalidArgument(format!(
+                    "Line {number}: move-focus --create can not be batched"
+                )))
+            }
+            Commands::CreateWorkspace { print_id: true, .. } => {
+                return Err(Error::InvalidArgument(format!(
+                    "Line {number}: create-workspace --print-id can not be batched"
+                )))
+            }
+            command => commands.push(command),
+        }
+    }
+    Ok(commands)
+}
+
+// the new workspace is only known once the compositor announced it, so creating it needs its own commit
+fn create_at_edge<'a>(
+    client: &'a mut WorkspaceClient,
+    output: Option<&OutputSelector>,
+    name: &str,
+    timeout: Duration,
+) -> Result<&'a Workspace, Error> {
+    let group = client.selected_group(output)?;
+    if !group.capabilities.contains(GroupCapabilities::CreateWorkspace) {
+        return Err(Error::CapabilityMissing(
+            "Output does not advertise the create_workspace capability".to_string(),
+        ));
+    }
+    let group = group.handle.clone();
+    create_and_find(client, &group, name, timeout)
+}
+
+/// Creates the workspace with its own commit and waits until the compositor announced it.
+fn create_and_find<'a>(
+    client: &'a mut WorkspaceClient,
+    group: &GroupHandle,
+    name: &str,
+    timeout: Duration,
+) -> Result<&'a Workspace, Error> {
+    let known = client
+        .state()
+        .workspaces
+        .iter()
+        .map(|ws| ws.handle.clone())
+        .collect::<Vec<_>>();
+    let expectation = Expectation::created(client.state(), group, name);
+    group.create_workspace(name.to_string());
+    client.commit_and_wait(&[expectation], timeout)?;
+    client
+        .state()
+        .workspaces
+        .iter()
+        .find(|ws| {
+            ws.group.as_ref() == Some(group)
+                && ws.name.as_deref() == Some(name)
+                && !known.contains(&ws.handle)
+        })
+        .ok_or(Error::Rejected(format!("Created workspace {name} not found")))
+}
+
+fn format_plan(plan: &[Step], json: bool) -> String {
+    if json {
+        format!("{}\n", serde_json::json!(plan))
+    } else {
+        plan.iter().map(|step| format!("{step}\n")).collect()
+    }
+}
+
+fn list_data(client: &mut WorkspaceClient, args: &ListArgs, json: bool) -> Result<String, Error> {
+    let group_filter = match &args.output {
+        Some(output) => Some(client.group(output)?.handle.clone()),
+        None => None,
+    };
+    let workspace_state = client.state_mut();
+    // the daemon keeps serving from this state, only a copy is sorted and filtered. The creation
+    // order is only known from the original.
+    let (groups, workspaces) = (workspace_state.groups.clone(), workspace_state.workspaces.clone());
+    workspace_state.sort_workspaces();
+    workspace_state.sort_groups_by_id();
+    if let Some(group_filter) = group_filter {
+        workspace_state
+            .workspaces
+            .retain(|ws| ws.group.as_ref().is_some_and(|g| g == &group_filter));
+        workspace_state.groups.retain(|g| g.handle == group_filter);
+    };
 
-    Ok(())
+    let output = if args.json || json {
+        match serde_json::to_string(&workspace_state) {
+            Ok(json) => format!("{json}\n"),
+            Err(e) => format!("{e}\n"),
+        }
+    } else {
+        workspace_state.to_string()
+    };
+    workspace_state.groups = groups;
+    workspace_state.workspaces = workspaces;
+    Ok(output)
 }
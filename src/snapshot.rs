@@ -0,0 +1,222 @@
+use std::fmt::Display;
+
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::layout::Step;
+use crate::workspace_backend::GroupHandle;
+use crate::workspace_state::{
+    output_name, Workspace, WorkspaceCapabilities, WorkspaceState, WorkspaceStates,
+};
+
+/// Workspace arrangement written by `save` and read back by `restore`. Unlike the `list` output it
+/// only contains what identifies outputs and workspaces across compositor restarts.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub outputs: Vec<OutputSnapshot>,
+    /// Workspaces without output. None of the protocols can create a workspace without output,
+    /// so restore only reports the ones that are missing.
+    #[serde(default)]
+    pub unassigned: Vec<WorkspaceSnapshot>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutputSnapshot {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub workspaces: Vec<WorkspaceSnapshot>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkspaceSnapshot {
+    /// Stable id, only sent by ext-v1.
+    pub id: Option<String>,
+    pub name: Option<String>,
+    #[serde(default)]
+    pub coordinates: Vec<u32>,
+    #[serde(default)]
+    pub active: bool,
+}
+
+type Matcher = fn(&WorkspaceSnapshot, &Workspace, &GroupHandle) -> bool;
+
+// earlier passes win, so a workspace matched by id is not taken by another one with the same name
+const MATCHERS: [Matcher; 4] = [
+    |saved, ws, _| saved.id.is_some() && saved.id == ws.id,
+    |saved, ws, group| {
+        saved.name.is_some() && saved.name == ws.name && ws.group.as_ref() == Some(group)
+    },
+    |saved, ws, _| saved.name.is_some() && saved.name == ws.name,
+    // coordinates are only unique per output and only identify workspaces without name
+    |saved, ws, group| {
+        saved.name.is_none()
+            && !saved.coordinates.is_empty()
+            && saved.coordinates == ws.coordinates
+            && ws.group.as_ref() == Some(group)
+    },
+];
+
+impl Snapshot {
+    /// Workspaces of each output in the `--order` of `state`.
+    pub fn capture(state: &WorkspaceState) -> Self {
+        let mut groups = state.groups.iter().collect::<Vec<_>>();
+        groups.sort_by_key(|group| group.id());
+        let workspaces = |group: Option<&GroupHandle>| {
+            let mut workspaces = state
+                .workspaces
+                .iter()
+                .filter(|ws| ws.group.as_ref() == group)
+                .collect::<Vec<_>>();
+            workspaces.sort_by(|a, b| state.order.compare(a, b));
+            workspaces
+                .into_iter()
+                .map(|ws| WorkspaceSnapshot {
+                    id: ws.id.clone(),
+                    name: ws.name.clone(),
+                    coordinates: ws.coordinates.clone(),
+                    active: ws.state.contains(WorkspaceStates::Active),
+                })
+                .collect()
+        };
+        let outputs = groups
+            .into_iter()
+            .map(|group| {
+                let info = group.get_output_info();
+                OutputSnapshot {
+                    name: info.as_ref().and_then(|info| info.name.clone()),
+                    description: info.and_then(|info| info.description),
+                    workspaces: workspaces(Some(&group.handle)),
+                }
+            })
+            .collect();
+        Snapshot {
+            outputs,
+            unassigned: workspaces(None),
+        }
+    }
+
+    /// Steps that reproduce the snapshot in `state`. Outputs are matched by name, then by
+    /// description. Saved workspaces are matched by ext-v1 id, then by name (on the same output
+    /// first) and unnamed ones by coordinates. Matched workspaces on the wrong output are assigned,
+    /// unmatched ones created and saved active ones activated. Workspaces without the assign
+    /// capability are only matched on their saved output. Outputs that are not connected, unnamed
+    /// workspaces that can not be found and saved workspaces without output are skipped.
+    pub fn plan(&self, state: &WorkspaceState) -> Vec<Step> {
+        let mut targets = Vec::new();
+        for output in &self.outputs {
+            match find_group(state, output) {
+                Some((label, group)) => {
+                    targets.extend(output.workspaces.iter().map(|ws| (label.clone(), group, ws)))
+                }
+                None => warn!("{output} is not connected, skipping its workspaces"),
+            }
+        }
+
+        let mut matched = vec![None::<&Workspace>; targets.len()];
+        let mut claimed = Vec::new();
+        for matches in MATCHERS {
+            for ((_, group, saved), slot) in targets.iter().zip(matched.iter_mut()) {
+                if slot.is_some() {
+                    continue;
+                }
+                *slot = state
+                    .workspaces
+                    .iter()
+                    .find(|ws| {
+                        !claimed.contains(&&ws.handle)
+                            && (ws.group.as_ref() == Some(*group)
+                                || ws.capabilities.contains(WorkspaceCapabilities::Assign))
+                            && matches(saved, ws, group)
+                    });
+                if let Some(ws) = slot {
+                    claimed.push(&ws.handle);
+                }
+            }
+        }
+
+        for saved in &self.unassigned {
+            let exists = state.workspaces.iter().any(|ws| {
+                (saved.id.is_some() && saved.id == ws.id)
+                    || (saved.name.is_some() && saved.name == ws.name)
+            });
+            if !exists {
+                warn!(
+                    "workspace {} without output not found, skipping it",
+                    saved.name.as_deref().unwrap_or("(unnamed)")
+                );
+            }
+        }
+
+        let (mut changes, mut activations) = (Vec::new(), Vec::new());
+        for ((output, group, saved), workspace) in targets.into_iter().zip(matched) {
+            let label = match (&saved.name, workspace) {
+                (Some(name), _) => name.clone(),
+                (None, Some(ws)) => ws.id().to_string(),
+                (None, None) => {
+                    warn!(
+                        "unnamed workspace {:?} on {output} not found, skipping it",
+                        saved.coordinates
+                    );
+                    continue;
+                }
+            };
+            match workspace {
+                Some(ws) if ws.group.as_ref() != Some(group) => changes.push(Step::Assign {
+                    workspace: label.clone(),
+                    from: ws.group.as_ref().and_then(|g| output_name(&state.groups, g)),
+                    output: output.clone(),
+                    handle: ws.handle.clone(),
+                    group: group.clone(),
+                }),
+                Some(_) => {}
+                None => changes.push(Step::Create {
+                    workspace: label.clone(),
+                    output: output.clone(),
+                    group: group.clone(),
+                }),
+            }
+            let active = workspace.is_some_and(|ws| ws.state.contains(WorkspaceStates::Active));
+            if saved.active && !active {
+                activations.push(Step::Activate {
+                    workspace: label,
+                    output,
+                    handle: workspace.map(|ws| ws.handle.clone()),
+                    group: group.clone(),
+                });
+            }
+        }
+        changes.sort_by_key(|step| matches!(step, Step::Create { .. }));
+        changes.extend(activations);
+        changes
+    }
+}
+
+fn find_group<'a>(
+    state: &'a WorkspaceState,
+    output: &OutputSnapshot,
+) -> Option<(String, &'a GroupHandle)> {
+    let infos = state
+        .groups
+        .iter()
+        .filter_map(|group| group.get_output_info().map(|info| (info, &group.handle)))
+        .collect::<Vec<_>>();
+    let (info, group) = infos
+        .iter()
+        .find(|(info, _)| output.name.is_some() && info.name == output.name)
+        .or_else(|| {
+            infos.iter().find(|(info, _)| {
+                output.description.is_some() && info.description == output.description
+            })
+        })?;
+    Some((info.name.clone().unwrap_or_else(|| output.to_string()), *group))
+}
+
+impl Display for OutputSnapshot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (&self.name, &self.description) {
+            (Some(name), _) => write!(f, "output {name}"),
+            (None, Some(description)) => write!(f, "output \"{description}\""),
+            (None, None) => write!(f, "unnamed output"),
+        }
+    }
+}
@@ -0,0 +1,244 @@
+use crate::workspace_backend::GroupHandle;
+use crate::workspace_state::{
+    Batch, Checkpoint, TilingState, Workspace, WorkspaceCapabilities, WorkspaceGroup,
+    WorkspaceStates,
+};
+
+/// Typed change of a single workspace or group between two checkpoints. Workspaces and groups are
+/// as they were after the change, removed ones and groups that left their output as they were
+/// before.
+#[derive(Debug, Clone)]
+pub enum Transition {
+    OutputEntered(WorkspaceGroup),
+    OutputLeft(WorkspaceGroup),
+    Created(Workspace),
+    Removed(Workspace),
+    Activated(Workspace),
+    Deactivated(Workspace),
+    BecameUrgent(Workspace),
+    UrgencyCleared(Workspace),
+    Renamed {
+        workspace: Workspace,
+        from: Option<String>,
+    },
+    MovedBetweenGroups {
+        workspace: Workspace,
+        from: Option<GroupHandle>,
+    },
+    CapabilitiesChanged {
+        workspace: Workspace,
+        from: WorkspaceCapabilities,
+    },
+    TilingChanged {
+        workspace: Workspace,
+        from: Option<TilingState>,
+    },
+}
+
+impl Batch {
+    pub fn transitions(&self) -> Vec<Transition> {
+        transitions(&self.before, &self.after)
+    }
+}
+
+/// Transitions from `before` to `after`: outputs left and entered, then for each workspace its
+/// creation, renaming, move, capabilities, tiling state, activation and urgency, and finally removed
+/// workspaces. A workspace created active or urgent is also reported as activated or urgent.
+pub fn transitions(before: &Checkpoint, after: &Checkpoint) -> Vec<Transition> {
+    let mut transitions = Vec::new();
+
+    for group in &before.groups {
+        let kept = after.groups.iter().find(|g| g.handle == group.handle);
+        if group.output.is_some() && kept.is_none_or(|g| g.output != group.output) {
+            transitions.push(Transition::OutputLeft(group.clone()));
+        }
+    }
+    for group in &after.groups {
+        let previous = before.groups.iter().find(|g| g.handle == group.handle);
+        if group.output.is_some() && previous.is_none_or(|g| g.output != group.output) {
+            transitions.push(Transition::OutputEntered(group.clone()));
+        }
+    }
+
+    for workspace in &after.workspaces {
+        let previous = before.workspaces.iter().find(|ws| ws.handle == workspace.handle);
+        match previous {
+            None => transitions.push(Transition::Created(workspace.clone())),
+            Some(previous) => {
+                if previous.name != workspace.name {
+                    transitions.push(Transition::Renamed {
+                        workspace: workspace.clone(),
+                        from: previous.name.clone(),
+                    });
+                }
+                if previous.group != workspace.group {
+                    transitions.push(Transition::MovedBetweenGroups {
+                        workspace: workspace.clone(),
+                        from: previous.group.clone(),
+                    });
+                }
+                if previous.capabilities != workspace.capabilities {
+                    transitions.push(Transition::CapabilitiesChanged {
+                        workspace: workspace.clone(),
+                        from: previous.capabilities.clone(),
+                    });
+                }
+                if previous.tiling_state != workspace.tiling_state {
+                    transitions.push(Transition::TilingChanged {
+                        workspace: workspace.clone(),
+                        from: previous.tiling_state,
+                    });
+                }
+            }
+        }
+        let was = previous.map_or(WorkspaceStates::empty(), |ws| ws.state.clone());
+        let flips = |flag: WorkspaceStates| {
+            (was.contains(flag.clone()), workspace.state.contains(flag))
+        };
+        match flips(WorkspaceStates::Active) {
+            (false, true) => transitions.push(Transition::Activated(workspace.clone())),
+            (true, false) => transitions.push(Transition::Deactivated(workspace.clone())),
+            _ => {}
+        }
+        match flips(WorkspaceStates::Urgent) {
+            (false, true) => transitions.push(Transition::BecameUrgent(workspace.clone())),
+            (true, false) => transitions.push(Transition::UrgencyCleared(workspace.clone())),
+            _ => {}
+        }
+    }
+
+    for workspace in &before.workspaces {
+        if !after.workspaces.iter().any(|ws| ws.handle == workspace.handle) {
+            transitions.push(Transition::Removed(workspace.clone()));
+        }
+    }
+    transitions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::workspace_state::tests::{apply, group, output, state, workspace};
+    use crate::workspace_state::{WorkspaceEvent, WorkspaceState};
+
+    // applies each batch to the state and returns the transitions of the last one
+    fn record(batches: Vec<Vec<WorkspaceEvent>>) -> Vec<String> {
+        let mut state = WorkspaceState {
+            batches: Some(Vec::new()),
+            ..state()
+        };
+        for events in batches {
+            apply(&mut state, events);
+        }
+        let batch = state.batches.unwrap().pop().unwrap();
+        batch.transitions().iter().map(describe).collect()
+    }
+
+    fn describe(transition: &Transition) -> String {
+        match transition {
+            Transition::OutputEntered(group) => format!("output entered {}", group.id()),
+            Transition::OutputLeft(group) => format!("output left {}", group.id()),
+            Transition::Created(ws) => format!("created {}", ws.id()),
+            Transition::Removed(ws) => format!("removed {}", ws.id()),
+            Transition::Activated(ws) => format!("activated {}", ws.id()),
+            Transition::Deactivated(ws) => format!("deactivated {}", ws.id()),
+            Transition::BecameUrgent(ws) => format!("urgent {}", ws.id()),
+            Transition::UrgencyCleared(ws) => format!("urgency cleared {}", ws.id()),
+            Transition::Renamed { workspace, from } => {
+                format!("renamed {} from {from:?}", workspace.id())
+            }
+            Transition::MovedBetweenGroups { workspace, from } => format!(
+                "moved {} from {:?} to {:?}",
+                workspace.id(),
+                from.as_ref().map(|g| g.protocol_id()),
+                workspace.group.as_ref().map(|g| g.protocol_id())
+            ),
+            Transition::CapabilitiesChanged { workspace, from } => {
+                format!("capabilities {} from {from:?}", workspace.id())
+            }
+            Transition::TilingChanged { workspace, from } => {
+                format!("tiling {} from {from:?}", workspace.id())
+            }
+        }
+    }
+
+    fn setup() -> Vec<WorkspaceEvent> {
+        vec![
+            WorkspaceEvent::WorkspaceGroupCreated(group(1)),
+            WorkspaceEvent::WorkspaceGroupCreated(group(2)),
+            WorkspaceEvent::WorkspaceCreated(Some(group(1)), workspace(3)),
+            WorkspaceEvent::WorkspaceName(workspace(3), "web".to_string()),
+            WorkspaceEvent::WorkspaceState(workspace(3), WorkspaceStates::Active),
+            WorkspaceEvent::WorkspaceCreated(Some(group(1)), workspace(4)),
+        ]
+    }
+
+    #[test]
+    fn first_batch_creates_everything() {
+        assert_eq!(record(vec![setup()]), ["created 3", "activated 3", "created 4"]);
+    }
+
+    #[test]
+    fn unchanged_batch_has_no_transitions() {
+        let name = WorkspaceEvent::WorkspaceName(workspace(3), "web".to_string());
+        assert!(record(vec![setup(), vec![name]]).is_empty());
+    }
+
+    #[test]
+    fn state_changes() {
+        let transitions = record(vec![
+            setup(),
+            vec![
+                WorkspaceEvent::WorkspaceState(workspace(3), WorkspaceStates::empty()),
+                WorkspaceEvent::WorkspaceState(
+                    workspace(4),
+                    WorkspaceStates::Active | WorkspaceStates::Urgent,
+                ),
+            ],
+        ]);
+        assert_eq!(transitions, ["deactivated 3", "activated 4", "urgent 4"]);
+    }
+
+    #[test]
+    fn property_changes() {
+        let transitions = record(vec![
+            setup(),
+            vec![
+                WorkspaceEvent::WorkspaceName(workspace(3), "mail".to_string()),
+                WorkspaceEvent::WorkspaceLeave(workspace(4), group(1)),
+                WorkspaceEvent::WorkspaceEnter(workspace(4), group(2)),
+                WorkspaceEvent::WorkspaceCapabilities(workspace(4), WorkspaceCapabilities::Rename),
+                WorkspaceEvent::WorkspaceTilingState(workspace(4), TilingState::TilingEnabled),
+            ],
+        ]);
+        assert_eq!(
+            transitions,
+            [
+                "renamed 3 from Some(\"web\")",
+                "moved 4 from Some(1) to Some(2)",
+                "capabilities 4 from WorkspaceCapabilities(0)",
+                "tiling 4 from None",
+            ]
+        );
+    }
+
+    #[test]
+    fn removals_and_outputs() {
+        let (_conn, output) = output();
+        let mut first = setup();
+        first.push(WorkspaceEvent::OutputEnter(group(1), output.clone()));
+        let transitions = record(vec![
+            first,
+            vec![
+                WorkspaceEvent::OutputLeave(group(1), output.clone()),
+                WorkspaceEvent::OutputEnter(group(2), output),
+                WorkspaceEvent::WorkspaceRemoved(workspace(3)),
+                WorkspaceEvent::WorkspaceCreated(Some(group(2)), workspace(5)),
+            ],
+        ]);
+        assert_eq!(
+            transitions,
+            ["output left 1", "output entered 2", "created 5", "removed 3"]
+        );
+    }
+}
@@ -0,0 +1,130 @@
+use serde::Serialize;
+
+use crate::transition::Transition;
+use crate::workspace_state::{output_name, Batch, Checkpoint, Workspace};
+
+/// Semantic change of a batch, printed by `watch` as one json line.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Change {
+    WorkspaceCreated {
+        workspace: WorkspaceRef,
+    },
+    WorkspaceRemoved {
+        workspace: WorkspaceRef,
+    },
+    Activated {
+        workspace: WorkspaceRef,
+    },
+    Deactivated {
+        workspace: WorkspaceRef,
+    },
+    Urgent {
+        workspace: WorkspaceRef,
+    },
+    UrgentCleared {
+        workspace: WorkspaceRef,
+    },
+    Renamed {
+        workspace: WorkspaceRef,
+        from: Option<String>,
+    },
+    /// Assigned to another group, `from` and `to` are output names.
+    Moved {
+        workspace: WorkspaceRef,
+        from: Option<String>,
+        to: Option<String>,
+    },
+    OutputEnter {
+        output: Option<String>,
+        #[serde(rename = "groupProtocolId")]
+        group: u32,
+    },
+    OutputLeave {
+        output: Option<String>,
+        #[serde(rename = "groupProtocolId")]
+        group: u32,
+    },
+}
+
+/// Identifies a workspace in a [`Change`], as it was after the batch. Removed workspaces are
+/// identified as they were before.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspaceRef {
+    pub name: Option<String>,
+    /// Stable id, only sent by ext-v1.
+    pub id: Option<String>,
+    pub protocol_id: u32,
+    pub output: Option<String>,
+}
+
+impl WorkspaceRef {
+    fn new(checkpoint: &Checkpoint, workspace: &Workspace) -> Self {
+        WorkspaceRef {
+            name: workspace.name.clone(),
+            id: workspace.id.clone(),
+            protocol_id: workspace.id(),
+            output: workspace
+                .group
+                .as_ref()
+                .and_then(|group| output_name(&checkpoint.groups, group)),
+        }
+    }
+}
+
+/// Changes of the batch, in the order of its [`Transition`]s. Capability and tiling state changes
+/// are not reported.
+pub fn changes(batch: &Batch) -> Vec<Change> {
+    let Batch { before, after } = batch;
+    batch
+        .transitions()
+        .into_iter()
+        .filter_map(|transition| {
+            let workspace = |ws: &Workspace| WorkspaceRef::new(after, ws);
+            Some(match transition {
+                Transition::OutputEntered(group) => Change::OutputEnter {
+                    output: group.get_output_name(),
+                    group: group.id(),
+                },
+                Transition::OutputLeft(group) => Change::OutputLeave {
+                    output: group.get_output_name(),
+                    group: group.id(),
+                },
+                Transition::Created(ws) => Change::WorkspaceCreated {
+                    workspace: workspace(&ws),
+                },
+                Transition::Removed(ws) => Change::WorkspaceRemoved {
+                    workspace: WorkspaceRef::new(before, &ws),
+                },
+                Transition::Activated(ws) => Change::Activated {
+                    workspace: workspace(&ws),
+                },
+                Transition::Deactivated(ws) => Change::Deactivated {
+                    workspace: workspace(&ws),
+                },
+                Transition::BecameUrgent(ws) => Change::Urgent {
+                    workspace: workspace(&ws),
+                },
+                Transition::UrgencyCleared(ws) => Change::UrgentCleared {
+                    workspace: workspace(&ws),
+                },
+                Transition::Renamed { workspace: ws, from } => Change::Renamed {
+                    workspace: workspace(&ws),
+                    from,
+                },
+                Transition::MovedBetweenGroups { workspace: ws, from } => {
+                    let workspace = workspace(&ws);
+                    Change::Moved {
+                        from: from.and_then(|group| output_name(&before.groups, &group)),
+                        to: workspace.output.clone(),
+                        workspace,
+                    }
+                }
+                Transition::CapabilitiesChanged { .. } | Transition::TilingChanged { .. } => {
+                    return None
+                }
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,106 @@
+//! Protocol independent requests on the workspace manager, groups and workspaces. Each protocol
+//! module implements the traits for its objects, the rest of the crate only calls through them.
+//!
+//! The traits only cover requests. Everything else is still tied to Wayland: a `WorkspaceGroup`
+//! stores the `WlOutput` it is shown on, and `WorkspaceClient` dispatches events from an
+//! `EventQueue` of the wayland connection. A backend that does not speak one of the Wayland
+//! workspace protocols can not be plugged in without abstracting those too.
+
+use std::any::Any;
+use std::fmt::Debug;
+use std::ops::Deref;
+use std::sync::Arc;
+
+use crate::error::Error;
+use crate::workspace_state::TilingState;
+
+/// Type erased protocol object that can still be compared and downcast to its concrete type.
+pub trait AnyHandle: Any + Debug + Send + Sync {
+    fn as_any(&self) -> &dyn Any;
+    fn eq_handle(&self, other: &dyn Any) -> bool;
+}
+
+impl<T: Any + Debug + PartialEq + Send + Sync> AnyHandle for T {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    fn eq_handle(&self, other: &dyn Any) -> bool {
+        other.downcast_ref::<T>().is_some_and(|other| other == self)
+    }
+}
+
+/// Requests on the workspace manager. Implemented by every protocol module for its manager object.
+pub trait ManagerBackend: AnyHandle {
+    fn commit(&self);
+    fn stop(&self);
+}
+
+/// Requests on a workspace group. Implemented by every protocol module for its group handle.
+pub trait GroupBackend: AnyHandle {
+    fn protocol_id(&self) -> u32;
+    fn create_workspace(&self, name: String);
+    fn destroy(&self);
+}
+
+/// Requests on a workspace. Implemented by every protocol module for its workspace handle.
+pub trait WorkspaceBackend: AnyHandle {
+    fn protocol_id(&self) -> u32;
+    fn activate(&self);
+    fn deactivate(&self);
+    fn remove(&self);
+    fn destroy(&self);
+    fn assign(&self, _group: &GroupHandle) -> Result<(), Error> {
+        Err(Error::ProtocolUnsupported("assign request not supported by used protocol".to_string()))
+    }
+    fn rename(&self, _name: String) -> Result<(), Error> {
+        Err(Error::ProtocolUnsupported("rename request not supported by used protocol".to_string()))
+    }
+    fn set_tiling_state(&self, _state: TilingState) -> Result<(), Error> {
+        Err(Error::ProtocolUnsupported(
+            "set_tiling_state request not supported by used protocol".to_string(),
+        ))
+    }
+}
+
+macro_rules! backend_handle {
+    ($(#[$attr:meta])* $name:ident, $ptr:ident, $backend:ident) => {
+        $(#[$attr])*
+        pub struct $name($ptr<dyn $backend>);
+
+        impl $name {
+            pub fn new(backend: impl $backend) -> Self {
+                $name($ptr::new(backend))
+            }
+
+            pub fn downcast_ref<T: $backend>(&self) -> Option<&T> {
+                self.0.as_any().downcast_ref()
+            }
+        }
+
+        impl Deref for $name {
+            type Target = dyn $backend;
+
+            fn deref(&self) -> &Self::Target {
+                self.0.as_ref()
+            }
+        }
+
+        impl PartialEq for $name {
+            fn eq(&self, other: &Self) -> bool {
+                self.0.eq_handle(other.0.as_any())
+            }
+        }
+
+        impl Eq for $name {}
+
+        impl Debug for $name {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                self.0.fmt(f)
+            }
+        }
+    };
+}
+
+backend_handle!(ManagerHandle, Box, ManagerBackend);
+backend_handle!(#[derive(Clone)] GroupHandle, Arc, GroupBackend);
+backend_handle!(#[derive(Clone)] WorkspaceHandle, Arc, WorkspaceBackend);
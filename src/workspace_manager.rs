@@ -1,93 +1,320 @@
-use log::warn;
+use log::debug;
+use rustix::event::{poll, PollFd, PollFlags, Timespec};
+use rustix::io::Errno;
+use std::io::ErrorKind;
+use std::os::fd::BorrowedFd;
+use std::time::{Duration, Instant};
 use smithay_client_toolkit::globals::GlobalData;
-use wayland_client::WEnum;
 
-use std::error::Error;
-use std::fmt::Display;
-use std::fmt::Write;
-
-use crate::cli::{Cli, Commands, ListArgs, OutputSelector, WorkspaceSelector};
-use crate::ext::workspace;
+use crate::cli::{
+    Direction, OutputSelector, StateFilter, TargetOutput, WorkspaceArgs, WorkspaceSelector,
+};
+use regex::Regex;
+use crate::error::Error;
+use crate::expectation::Expectation;
 use crate::workspace_state::{
-    GroupCapabilities, Workspace, WorkspaceCapabilities, WorkspaceEvent, WorkspaceGroup,
-    WorkspaceHandler, WorkspaceStates,
+    Workspace, WorkspaceGroup, WorkspaceHandler, WorkspaceStates,
 };
-use crate::workspace_state::{ManagerHandle, Protocol, WorkspaceState};
+use crate::ext::workspace::{
+    cosmic_v1::client::zcosmic_workspace_manager_v1::ZcosmicWorkspaceManagerV1,
+    ext_v0::client::zext_workspace_manager_v1::ZextWorkspaceManagerV1,
+    ext_v1::client::ext_workspace_manager_v1::ExtWorkspaceManagerV1,
+};
+use crate::workspace_backend::ManagerHandle;
+use crate::workspace_state::{OutputArea, Protocol, WorkspaceOrder, WorkspaceState};
 use crate::{delegate_workspace_cosmic_v1, delegate_workspace_ext_v0, delegate_workspace_ext_v1};
 use smithay_client_toolkit::{
     delegate_output, delegate_registry,
-    output::{OutputHandler, OutputState},
+    output::{OutputHandler, OutputInfo, OutputState},
     registry::{ProvidesRegistryState, RegistryState},
     registry_handlers,
 };
 use wayland_client::{
-    globals::registry_queue_init, protocol::wl_output, Connection, EventQueue, Proxy, QueueHandle,
+    backend::WaylandError,
+    globals::{registry_queue_init, BindError},
+    protocol::wl_output,
+    Connection, Dispatch, EventQueue, Proxy, QueueHandle,
 };
-impl WorkspaceManager {
-    pub fn exec(args: &Cli) -> Result<(), Box<dyn Error>> {
-        let (registry_state, workspace_state, output_state, mut events) =
-            setup(args).expect("Failed to setup wayland socket connection!");
-
-        let mut workspace_manager = WorkspaceManager {
-            registry_state,
-            workspace_state,
-            output_state,
+
+/// Connection to the workspace manager of the compositor, independent of the bound protocol.
+pub struct WorkspaceClient {
+    events: EventQueue<WorkspaceManager>,
+    manager: WorkspaceManager,
+}
+
+impl WorkspaceClient {
+    /// Connects to the compositor named by the environment. Without an explicit protocol the first
+    /// workspace manager advertised by the compositor is bound.
+    pub fn connect(protocol: Option<Protocol>) -> Result<Self, Error> {
+        let conn = Connection::connect_to_env()?;
+        Self::from_connection(&conn, protocol)
+    }
+
+    /// Binds the workspace manager on an existing connection and waits for the initial state.
+    pub fn from_connection(
+        conn: &Connection,
+        protocol: Option<Protocol>,
+    ) -> Result<Self, Error> {
+        let (registry_state, workspace_state, output_state, events) = setup(conn, protocol)?;
+        let mut client = WorkspaceClient {
+            events,
+            manager: WorkspaceManager {
+                registry_state,
+                workspace_state,
+                output_state,
+            },
         };
-        events.roundtrip(&mut workspace_manager)?;
-        match &args.command {
-            Commands::List(args) => {
-                workspace_manager.list_data(&args)?;
-                return Ok(());
+        client.roundtrip()?;
+        debug!(
+            "initial state with {} groups and {} workspaces",
+            client.state().groups.len(),
+            client.state().workspaces.len()
+        );
+        Ok(client)
+    }
+
+    pub fn state(&self) -> &WorkspaceState {
+        &self.manager.workspace_state
+    }
+
+    pub fn state_mut(&mut self) -> &mut WorkspaceState {
+        &mut self.manager.workspace_state
+    }
+
+    pub fn workspace(
+        &self,
+        selector: &WorkspaceSelector,
+        output: Option<&OutputSelector>,
+    ) -> Result<&Workspace, Error> {
+        self.check_selector(selector)?;
+        self.manager.workspace_from_selection(selector, output)
+    }
+
+    /// Workspaces selected by the arguments of a command, see
+    /// [`WorkspaceManager::workspaces_from_selection`].
+    pub fn workspaces(&self, args: &WorkspaceArgs) -> Result<Vec<&Workspace>, Error> {
+        self.check_selector(&args.workspace)?;
+        self.manager.workspaces_from_selection(
+            &args.workspace,
+            &args.filter,
+            args.output.as_ref(),
+            args.all,
+        )
+    }
+
+    pub fn matching_workspaces(
+        &self,
+        selector: Option<&WorkspaceSelector>,
+        output: Option<&OutputSelector>,
+    ) -> Result<Vec<&Workspace>, Error> {
+        if let Some(selector) = selector {
+            self.check_selector(selector)?;
+        }
+        self.manager.matching_workspaces(selector, output)
+    }
+
+    /// Fails unless the bound protocol sends stable workspace ids, which only ext-v1 does.
+    pub fn require_ids(&self) -> Result<(), Error> {
+        if self.state().protocol != Protocol::ExtV1 {
+            return Err(Error::ProtocolUnsupported(
+                "Workspace ids are only sent by ext-v1".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    // the other protocols would never match an id
+    fn check_selector(&self, selector: &WorkspaceSelector) -> Result<(), Error> {
+        if selector.matching.id.is_some() {
+            self.require_ids()?;
+        }
+        Ok(())
+    }
+
+    pub fn group(&self, output: &OutputSelector) -> Result<&WorkspaceGroup, Error> {
+        self.manager.group_from_output(output)
+    }
+
+    pub fn selected_group(&self, output: Option<&OutputSelector>) -> Result<&WorkspaceGroup, Error> {
+        self.manager.selected_group(output)
+    }
+
+    pub fn target_group(
+        &self,
+        workspace: &Workspace,
+        target: &TargetOutput,
+    ) -> Result<&WorkspaceGroup, Error> {
+        self.manager.target_group(workspace, target)
+    }
+
+    pub fn directional_workspace(
+        &self,
+        output: Option<&OutputSelector>,
+        direction: Direction,
+    ) -> Result<Option<&Workspace>, Error> {
+        self.manager.directional_workspace(output, direction)
+    }
+
+    /// Workspace before or after the active one on the output, in `list` order.
+    pub fn relative_workspace(
+        &self,
+        output: Option<&OutputSelector>,
+        forward: bool,
+        wrap: bool,
+        skip_hidden: bool,
+    ) -> Result<&Workspace, Error> {
+        self.manager
+            .relative_workspace(output, forward, wrap, skip_hidden)
+    }
+
+    /// Applies all pending requests and waits until the compositor processed them.
+    pub fn commit(&mut self) -> Result<(), Error> {
+        if self.is_finished() {
+            return Err(Error::Finished);
+        }
+        self.manager.workspace_state.commit();
+        self.roundtrip()
+    }
+
+    /// Fails if the compositor finished the manager before the roundtrip completed.
+    pub fn roundtrip(&mut self) -> Result<(), Error> {
+        self.events.roundtrip(&mut self.manager)?;
+        if self.is_finished() {
+            return Err(Error::Finished);
+        }
+        Ok(())
+    }
+
+    /// Asks the compositor to stop sending workspace events and waits for the `finished` event.
+    /// No requests can be sent afterwards.
+    pub fn stop(&mut self) -> Result<(), Error> {
+        if !self.is_finished() {
+            self.manager.workspace_state.manager.stop();
+        }
+        while !self.is_finished() {
+            self.events.blocking_dispatch(&mut self.manager)?;
+        }
+        Ok(())
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.manager.workspace_state.finished
+    }
+
+    pub fn blocking_dispatch(&mut self) -> Result<(), Error> {
+        self.events.blocking_dispatch(&mut self.manager)?;
+        Ok(())
+    }
+
+    /// Like [`WorkspaceClient::blocking_dispatch`], but returns after `timeout` even if the
+    /// compositor sent nothing. Returns the number of dispatched events.
+    pub fn dispatch_timeout(&mut self, timeout: Duration) -> Result<usize, Error> {
+        self.poll_dispatch(None, Some(timeout))
+            .map(|(dispatched, _)| dispatched)
+    }
+
+    /// Like [`WorkspaceClient::blocking_dispatch`], but also returns once `fd` is readable, e.g. a
+    /// socket served next to the wayland connection. Returns whether `fd` is readable.
+    pub fn dispatch_or_readable(&mut self, fd: BorrowedFd) -> Result<bool, Error> {
+        self.poll_dispatch(Some(fd), None)
+            .map(|(_, readable)| readable)
+    }
+
+    fn poll_dispatch(
+        &mut self,
+        other: Option<BorrowedFd>,
+        timeout: Option<Duration>,
+    ) -> Result<(usize, bool), Error> {
+        let dispatched = self.events.dispatch_pending(&mut self.manager)?;
+        if dispatched > 0 {
+            return Ok((dispatched, false));
+        }
+        self.events.flush()?;
+        let mut readable = false;
+        if let Some(guard) = self.events.prepare_read() {
+            let fd = guard.connection_fd();
+            let mut fds = vec![PollFd::new(&fd, PollFlags::IN | PollFlags::ERR)];
+            fds.extend(other.as_ref().map(|other| PollFd::new(other, PollFlags::IN | PollFlags::ERR)));
+            let timeout = timeout.and_then(|timeout| Timespec::try_from(timeout).ok());
+            loop {
+                match poll(&mut fds, timeout.as_ref()) {
+                    Err(Errno::INTR) => continue,
+                    result => break result,
+                }
             }
-            Commands::Listen => loop {
-                events.blocking_dispatch(&mut workspace_manager)?;
-            },
-            Commands::CreateWorkspace {
-                workspace_name,
-                output,
-            } => {
-                let group = workspace_manager.group_from_output(&output)?;
-                group.create_workspace(workspace_name.to_string())
+            .map_err(|e| Error::Connection(format!("Unable to poll wayland connection: {e}")))?;
+            readable = fds.get(1).is_some_and(|fd| !fd.revents().is_empty());
+            if !fds[0].revents().is_empty() {
+                match guard.read() {
+                    Err(WaylandError::Io(e)) if e.kind() == ErrorKind::WouldBlock => {}
+                    result => {
+                        result?;
+                    }
+                }
             }
-            Commands::Activate(args) => {
-                let workspace = workspace_manager
-                    .workspace_from_selection(&args.workspace, args.output.as_ref())?;
-                workspace.activate();
-                workspace_manager.workspace_state.commit();
+        }
+        Ok((self.events.dispatch_pending(&mut self.manager)?, readable))
+    }
+
+    /// Commits like [`WorkspaceClient::commit`] and then dispatches until the state shows all
+    /// expected outcomes. Fails with the first mismatch once `timeout` has passed.
+    pub fn commit_and_wait(
+        &mut self,
+        expectations: &[Expectation],
+        timeout: Duration,
+    ) -> Result<(), Error> {
+        let deadline = Instant::now() + timeout;
+        self.commit()?;
+        loop {
+            let Some(mismatch) = expectations.iter().find_map(|e| e.check(self.state()).err())
+            else {
+                return Ok(());
+            };
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(Error::Rejected(format!(
+                    "Compositor did not apply the change within {}ms: {mismatch}",
+                    timeout.as_millis()
+                )));
             }
-            Commands::Deactivate(args) => {
-                let workspace = workspace_manager
-                    .workspace_from_selection(&args.workspace, args.output.as_ref())?;
-                workspace.deactivate();
-                workspace_manager.workspace_state.commit();
+            self.dispatch_timeout(deadline - now)?;
+            if self.is_finished() {
+                return Err(Error::Finished);
             }
-            Commands::Remove(args) => {
-                let workspace = workspace_manager
-                    .workspace_from_selection(&args.workspace, args.output.as_ref())?;
-                workspace.remove();
-                workspace.destroy();
-                workspace_manager.workspace_state.commit();
+        }
+    }
+
+    /// Dispatches until `holds` is true for the client. Returns `false` if `timeout` passed first,
+    /// without timeout it waits as long as the compositor keeps the manager.
+    pub fn dispatch_until(
+        &mut self,
+        timeout: Option<Duration>,
+        holds: impl Fn(&Self) -> bool,
+    ) -> Result<bool, Error> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        while !holds(self) {
+            if self.is_finished() {
+                return Err(Error::Finished);
             }
-            Commands::Assign {
-                workspace_args,
-                target,
-            } => {
-                let workspace = workspace_manager.workspace_from_selection(
-                    &workspace_args.workspace,
-                    workspace_args.output.as_ref(),
-                )?;
-                let group = workspace_manager.group_from_output(&target.as_output_selection())?;
-                workspace.assign(&group.handle)?;
-                workspace_manager.workspace_state.commit();
+            match deadline {
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return Ok(false);
+                    }
+                    self.dispatch_timeout(deadline - now)?;
+                }
+                None => self.blocking_dispatch()?,
             }
         }
-        events.roundtrip(&mut workspace_manager)?;
-        Ok(())
+        Ok(true)
     }
 }
 
+
 fn setup(
-    args: &Cli,
+    conn: &Connection,
+    protocol: Option<Protocol>,
 ) -> Result<
     (
         RegistryState,
@@ -95,11 +322,10 @@ fn setup(
         OutputState,
         EventQueue<WorkspaceManager>,
     ),
-    Box<dyn Error>,
+    Error,
 > {
-    let conn = Connection::connect_to_env()?;
-
-    let (globals, events) = registry_queue_init(&conn)?;
+    let (globals, events) = registry_queue_init(conn)
+        .map_err(|e| Error::Connection(format!("Unable to read wayland globals: {e}")))?;
     let qh: QueueHandle<WorkspaceManager> = events.handle();
 
     let registry_state = RegistryState::new(&globals);
@@ -107,44 +333,44 @@ fn setup(
     let output_state = OutputState::new(&globals, &qh);
 
     let (protocol, manager) = {
-        if let Some(protocol) = &args.global_opts.protocol {
+        if let Some(protocol) = &protocol {
             match protocol {
                 Protocol::ExtV0 => (
                     protocol,
-                    ManagerHandle::ExtV0(
-                        registry_state
-                            .bind_one(&qh, 1..=1, GlobalData)
-                            .expect("failed to bind 'ext_workspace_manager_v0'"),
+                    ManagerHandle::new(
+                        bind_manager::<ZextWorkspaceManagerV1>(&registry_state, &qh).map_err(|e| {
+                            Error::ProtocolUnsupported(format!("Unable to bind 'ext_workspace_manager_v0': {e}"))
+                        })?,
                     ),
                 ),
                 Protocol::ExtV1 => (
                     protocol,
-                    ManagerHandle::ExtV1(
-                        registry_state
-                            .bind_one(&qh, 1..=1, GlobalData)
-                            .expect("failed to bind 'ext_workspace_manager_v1'"),
+                    ManagerHandle::new(
+                        bind_manager::<ExtWorkspaceManagerV1>(&registry_state, &qh).map_err(|e| {
+                            Error::ProtocolUnsupported(format!("Unable to bind 'ext_workspace_manager_v1': {e}"))
+                        })?,
                     ),
                 ),
                 Protocol::CosmicV1 => (
                     protocol,
-                    ManagerHandle::CosmicV1(
-                        registry_state
-                            .bind_one(&qh, 1..=1, GlobalData)
-                            .expect("failed to bind 'zcosmic_workspace_manager_v1'"),
+                    ManagerHandle::new(
+                        bind_manager::<ZcosmicWorkspaceManagerV1>(&registry_state, &qh).map_err(|e| {
+                            Error::ProtocolUnsupported(format!("Unable to bind 'zcosmic_workspace_manager_v1': {e}"))
+                        })?,
                     ),
                 ),
             }
         } else {
-            if let Ok(handle) = registry_state.bind_one(&qh, 1..=1, GlobalData) {
-                (&Protocol::ExtV0, ManagerHandle::ExtV0(handle))
-            } else if let Ok(handle) = registry_state.bind_one(&qh, 1..=1, GlobalData) {
-                (&Protocol::ExtV1, ManagerHandle::ExtV1(handle))
-            } else if let Ok(handle) = registry_state.bind_one(&qh, 1..=1, GlobalData) {
-                (&Protocol::CosmicV1, ManagerHandle::CosmicV1(handle))
+            if let Ok(handle) = bind_manager::<ZextWorkspaceManagerV1>(&registry_state, &qh) {
+                (&Protocol::ExtV0, ManagerHandle::new(handle))
+            } else if let Ok(handle) = bind_manager::<ExtWorkspaceManagerV1>(&registry_state, &qh) {
+                (&Protocol::ExtV1, ManagerHandle::new(handle))
+            } else if let Ok(handle) = bind_manager::<ZcosmicWorkspaceManagerV1>(&registry_state, &qh) {
+                (&Protocol::CosmicV1, ManagerHandle::new(handle))
             } else {
-                return Err(
-                    format!("unable to bind any workspace management protocol version").into(),
-                );
+                return Err(Error::ProtocolUnsupported(
+                    "unable to bind any workspace management protocol version".to_string(),
+                ));
             }
         }
     };
@@ -154,10 +380,27 @@ fn setup(
         manager,
         events: vec![],
         protocol: *protocol,
+        finished: false,
+        batches: None,
+        order: WorkspaceOrder::default(),
     };
     Ok((registry_state, workspace_state, output_state, events))
 }
 
+// binds the highest version supported by both sides, group and workspace handles inherit it
+fn bind_manager<I>(
+    registry_state: &RegistryState,
+    qh: &QueueHandle<WorkspaceManager>,
+) -> Result<I, BindError>
+where
+    I: Proxy + 'static,
+    WorkspaceManager: Dispatch<I, GlobalData>,
+{
+    let manager = registry_state.bind_one::<I, _, _>(qh, 1..=I::interface().version, GlobalData)?;
+    debug!("bound {} version {}", I::interface().name, manager.version());
+    Ok(manager)
+}
+
 pub struct WorkspaceManager {
     registry_state: RegistryState,
     workspace_state: WorkspaceState,
@@ -169,8 +412,71 @@ impl WorkspaceManager {
         &self,
         selector: &WorkspaceSelector,
         output: Option<&OutputSelector>,
-    ) -> Result<&Workspace, String> {
-        let mut workspaces = if let Some(output) = output {
+    ) -> Result<&Workspace, Error> {
+        self.workspaces_from_selection(selector, &StateFilter::default(), output, false)
+            .map(|matches| matches[0])
+    }
+
+    /// Workspaces matching the selection and the filter. Unless `all` is set the match has to be
+    /// unique. A filter without selection picks from all workspaces (on the output).
+    pub fn workspaces_from_selection(
+        &self,
+        selector: &WorkspaceSelector,
+        filter: &StateFilter,
+        output: Option<&OutputSelector>,
+        all: bool,
+    ) -> Result<Vec<&Workspace>, Error> {
+        if selector.is_empty() && filter.is_empty() {
+            return Err(Error::InvalidArgument(
+                "Select a workspace or filter by state".to_string(),
+            ));
+        }
+        let workspaces = self.output_workspaces(output)?;
+        if workspaces.is_empty() {
+            return Err(Error::NotFound("No workspaces (on selected output)".to_string()));
+        };
+        let (mut matches, mut selection) = match selector.is_empty() {
+            true => (workspaces, "workspace".to_string()),
+            false => select(workspaces, selector, self.workspace_state.order)?,
+        };
+        if !filter.is_empty() {
+            matches.retain(|ws| filter_matches(filter, ws));
+            selection = format!("{} {selection}", filter_label(filter));
+        }
+        if let (Some(index), []) = (selector.matching.index, matches.as_slice()) {
+            return Err(Error::NotFound(format!(
+                "Unable to find workspace with index {}",
+                index
+            )));
+        }
+        if !all {
+            return unique(matches, selection).map(|workspace| vec![workspace]);
+        }
+        if matches.is_empty() {
+            return Err(Error::NotFound(format!("Unable to find {selection}")));
+        }
+        Ok(matches)
+    }
+
+    /// Every workspace matching the selection, all on the output without selector. None if the
+    /// output does not exist.
+    pub fn matching_workspaces(
+        &self,
+        selector: Option<&WorkspaceSelector>,
+        output: Option<&OutputSelector>,
+    ) -> Result<Vec<&Workspace>, Error> {
+        match (self.output_workspaces(output), selector) {
+            (Ok(workspaces), Some(selector)) => {
+                Ok(select(workspaces, selector, self.workspace_state.order)?.0)
+            }
+            (Ok(workspaces), None) => Ok(workspaces),
+            (Err(Error::NotFound(_)), _) => Ok(Vec::new()),
+            (Err(e), _) => Err(e),
+        }
+    }
+
+    fn output_workspaces(&self, output: Option<&OutputSelector>) -> Result<Vec<&Workspace>, Error> {
+        Ok(if let Some(output) = output {
             let group = self.group_from_output(output)?;
             self.workspace_state
                 .workspaces
@@ -179,91 +485,364 @@ impl WorkspaceManager {
                 .collect::<Vec<_>>()
         } else {
             self.workspace_state.workspaces.iter().collect::<Vec<_>>()
-        };
-        if workspaces.len() == 0 {
-            return Err(format!("No workspaces (on selected output)"));
-        };
-        if selector.active {
-            return workspaces
-                .iter()
-                .find(|ws| ws.state.contains(WorkspaceStates::Active))
-                .map_or(Err(format!("Unable to find active workspace!")), |ws| {
-                    Ok(ws)
-                });
-        } else if let Some(index) = selector.index {
-            workspaces.sort_unstable_by(|a, b| a.id().cmp(&b.id()));
-            return workspaces.get(index).map_or(
-                Err(format!("Unable to find workspace with index {}", index)),
-                |w| Ok(w),
-            );
-        } else if let Some(name) = &selector.name {
-            return workspaces
-                .iter()
-                .find(|workspace| workspace.name.as_ref().is_some_and(|n| n == name))
-                .map_or(
-                    Err(format!("Unable to find workspace with name {name}")),
-                    |w| Ok(w),
-                );
-        } else if let Some(protocol_id) = selector.protocol_id {
-            return workspaces
-                .iter()
-                .find(|workspace| workspace.id() == protocol_id as u32)
-                .map_or(
-                    Err(format!(
-                        "Unable to find workspace with protocol id {protocol_id}"
-                    )),
-                    |w| Ok(w),
-                );
-        } else if let Some(coordinates) = &selector.coordinates {
-            let coords_len = workspaces.first().unwrap().coordinates.len();
-            if coords_len != coordinates.len() {
-                return Err(format!(
-                    "Wrong coordinate length/number of axis. Expected {coords_len}, got {}",
-                    coordinates.len()
-                ));
-            };
-            return workspaces
-                .iter()
-                .find(|workspace| workspace.coordinates == *coordinates)
-                .map_or(
-                    Err(format!(
-                        "Unable to find workspace with coordinates {coordinates:?}"
-                    )),
-                    |w| Ok(w),
-                );
+        })
+    }
+
+    /// Group of the selected output. Without selection the only group, if there is exactly one.
+    pub fn selected_group(&self, output: Option<&OutputSelector>) -> Result<&WorkspaceGroup, Error> {
+        match output {
+            Some(output) => self.group_from_output(output),
+            None => match self.workspace_state.groups.as_slice() {
+                [group] => Ok(group),
+                groups => Err(Error::InvalidArgument(format!(
+                    "Output selection required, {} workspace groups found",
+                    groups.len()
+                ))),
+            },
         }
+    }
 
-        return Err("No workspace handle for provided selector found!".to_string());
+    fn group_workspaces(&self, group: &WorkspaceGroup) -> Vec<&Workspace> {
+        let mut workspaces = self
+            .workspace_state
+            .workspaces
+            .iter()
+            .filter(|ws| ws.group.as_ref() == Some(&group.handle))
+            .collect::<Vec<_>>();
+        workspaces.sort_by(|a, b| self.workspace_state.order.compare(a, b));
+        workspaces
     }
 
-    pub fn group_from_output(&self, output: &OutputSelector) -> Result<&WorkspaceGroup, String> {
+    pub fn relative_workspace(
+        &self,
+        output: Option<&OutputSelector>,
+        forward: bool,
+        wrap: bool,
+        skip_hidden: bool,
+    ) -> Result<&Workspace, Error> {
+        let workspaces = self.group_workspaces(self.selected_group(output)?);
+        let active = active_workspace(&workspaces)?;
+        let start = workspaces.iter().position(|ws| ws.handle == active.handle).unwrap();
+
+        let len = workspaces.len() as isize;
+        let candidates = (1..len).map_while(|offset| {
+            let index = if forward { start as isize + offset } else { start as isize - offset };
+            if wrap {
+                Some(index.rem_euclid(len) as usize)
+            } else {
+                (0..len).contains(&index).then_some(index as usize)
+            }
+        });
+        candidates
+            .map(|index| workspaces[index])
+            .find(|ws| !(skip_hidden && ws.state.contains(WorkspaceStates::Hidden)))
+            .ok_or(Error::NotFound(format!(
+                "No {} workspace after {}",
+                if forward { "next" } else { "previous" },
+                active.name.as_deref().unwrap_or("active workspace")
+            )))
+    }
+
+    /// Nearest workspace from the active one in the direction on the coordinate grid of the
+    /// group. Workspaces in the same row/column come first, gaps in the grid are skipped. `None`
+    /// if the active workspace is at the edge.
+    pub fn directional_workspace(
+        &self,
+        output: Option<&OutputSelector>,
+        direction: Direction,
+    ) -> Result<Option<&Workspace>, Error> {
+        let workspaces = self.group_workspaces(self.selected_group(output)?);
+        let active = active_workspace(&workspaces)?;
+        let (axis, forward) = match direction {
+            Direction::Left => (0, false),
+            Direction::Right => (0, true),
+            Direction::Up => (1, false),
+            Direction::Down => (1, true),
+        };
+        if active.coordinates.len() <= axis {
+            return Err(Error::InvalidArgument(format!(
+                "Workspace coordinates have {} axes, moving {direction:?} needs {}",
+                active.coordinates.len(),
+                axis + 1
+            )));
+        }
+        let origin = &active.coordinates;
+        Ok(workspaces
+            .into_iter()
+            .filter(|ws| ws.coordinates.len() == origin.len())
+            .filter(|ws| {
+                if forward {
+                    ws.coordinates[axis] > origin[axis]
+                } else {
+                    ws.coordinates[axis] < origin[axis]
+                }
+            })
+            .min_by_key(|ws| {
+                let off_axis: u32 = (0..origin.len())
+                    .filter(|a| *a != axis)
+                    .map(|a| ws.coordinates[a].abs_diff(origin[a]))
+                    .sum();
+                (off_axis, ws.coordinates[axis].abs_diff(origin[axis]))
+            }))
+    }
+
+    pub fn group_from_output(&self, output: &OutputSelector) -> Result<&WorkspaceGroup, Error> {
         let groups = &self.workspace_state.groups;
         if let Some(name) = &output.output_name {
             return groups
                 .iter()
-                .find(|group| group.get_output_name().map_or(false, |n| &n == name))
-                .map_or(
-                    Err(format!("Unable to find output with name {}!", name)),
-                    |g| Ok(g),
-                );
+                .find(|group| group.get_output_name().is_some_and(|n| &n == name))
+                .ok_or(Error::NotFound(format!("Unable to find output with name {}!", name)));
         } else if let Some(protocol_id) = output.output_protocol_id {
             return groups
                 .iter()
                 .find(|group| {
-                    group.output.as_ref().map_or(false, |output| {
+                    group.output.as_ref().is_some_and(|output| {
                         output.id().protocol_id() == protocol_id as u32
                     })
                 })
-                .map_or(
-                    Err(format!(
-                        "Unable to find output with protocol id {}!",
-                        protocol_id
-                    )),
-                    |g| Ok(g),
-                );
-        } else {
-            return Err(format!("No output/group found for provided selection!"));
+                .ok_or(Error::NotFound(format!(
+                    "Unable to find output with protocol id {}!",
+                    protocol_id
+                )));
+        }
+
+        let infos = groups
+            .iter()
+            .filter_map(|group| group.get_output_info().map(|info| (group, info)))
+            .collect::<Vec<_>>();
+        let matching = |selection: String, matches: &dyn Fn(&OutputInfo) -> bool| {
+            unique_group(
+                infos.iter().filter(|(_, info)| matches(info)).map(|(g, _)| *g).collect(),
+                selection,
+            )
+        };
+        if let Some(make) = &output.output_make {
+            return matching(format!("output with make {make}"), &|info| &info.make == make);
+        } else if let Some(model) = &output.output_model {
+            return matching(format!("output with model {model}"), &|info| &info.model == model);
+        } else if let Some(text) = &output.output_description {
+            return matching(format!("output with description containing \"{text}\""), &|info| {
+                info.description.as_ref().is_some_and(|d| d.contains(text.as_str()))
+            });
+        }
+
+        let placed = self.placed_groups();
+        if let Some((x, y)) = output.output_at {
+            let covering = placed
+                .iter()
+                .filter(|(_, area)| area.contains(x, y))
+                .map(|(g, _)| *g)
+                .collect();
+            return unique_group(covering, format!("output at {x},{y}"));
+        } else if output.output_leftmost {
+            return placed.first().map(|(g, _)| *g).ok_or(Error::NotFound(
+                "Unable to find an output with known position!".to_string(),
+            ));
+        } else if output.output_rightmost {
+            return placed.last().map(|(g, _)| *g).ok_or(Error::NotFound(
+                "Unable to find an output with known position!".to_string(),
+            ));
+        } else if let Some(index) = output.output_index {
+            return nth(&placed, index).map(|(g, _)| *g).ok_or(Error::NotFound(format!(
+                "Unable to find output with index {index}!"
+            )));
+        }
+        Err(Error::InvalidArgument(
+            "No output/group found for provided selection!".to_string(),
+        ))
+    }
+
+    /// Groups of outputs with known position and their area, from left to right and top to bottom.
+    fn placed_groups(&self) -> Vec<(&WorkspaceGroup, OutputArea)> {
+        let mut placed = self
+            .workspace_state
+            .groups
+            .iter()
+            .filter_map(|group| group.get_output_area().map(|area| (group, area)))
+            .collect::<Vec<_>>();
+        placed.sort_by_key(|(_, area)| (area.x, area.y));
+        placed
+    }
+
+    /// Group `assign` moves the workspace to. `--direction` and `--next-output` are relative to
+    /// the output of the workspace.
+    pub fn target_group(
+        &self,
+        workspace: &Workspace,
+        target: &TargetOutput,
+    ) -> Result<&WorkspaceGroup, Error> {
+        if target.direction.is_none() && !target.next_output {
+            return self.group_from_output(&target.as_output_selection());
         }
+        let placed = self.placed_groups();
+        let origin = placed
+            .iter()
+            .position(|(group, _)| workspace.group.as_ref() == Some(&group.handle))
+            .ok_or(Error::NotFound(format!(
+                "Unable to find the output of workspace with protocol id {}!",
+                workspace.id()
+            )))?;
+        let (group, area) = placed[origin];
+        let output_name = group.get_output_name().unwrap_or_default();
+        if let Some(direction) = target.direction {
+            // nearest by distance of the centers
+            let center = |area: &OutputArea| {
+                (area.x as i64 * 2 + area.width as i64, area.y as i64 * 2 + area.height as i64)
+            };
+            let (x, y) = center(&area);
+            return placed
+                .iter()
+                .filter(|(_, other)| beyond(&area, other, direction))
+                .min_by_key(|(_, other)| {
+                    let (other_x, other_y) = center(other);
+                    (other_x - x).pow(2) + (other_y - y).pow(2)
+                })
+                .map(|(g, _)| *g)
+                .ok_or(Error::NotFound(format!("No output {direction:?} of {output_name}")));
+        }
+        match placed.len() {
+            1 => Err(Error::NotFound(format!("No output besides {output_name}"))),
+            len => Ok(placed[(origin + 1) % len].0),
+        }
+    }
+}
+
+// other starts past the edge of area in the direction, e.g. right of its right edge
+fn beyond(area: &OutputArea, other: &OutputArea, direction: Direction) -> bool {
+    match direction {
+        Direction::Left => other.x + other.width <= area.x,
+        Direction::Right => other.x >= area.x + area.width,
+        Direction::Up => other.y + other.height <= area.y,
+        Direction::Down => other.y >= area.y + area.height,
+    }
+}
+
+fn active_workspace<'a>(workspaces: &[&'a Workspace]) -> Result<&'a Workspace, Error> {
+    let active = workspaces
+        .iter()
+        .filter(|ws| ws.state.contains(WorkspaceStates::Active))
+        .copied()
+        .collect::<Vec<_>>();
+    unique(active, "active workspace".to_string())
+}
+
+// workspaces matching the selector and a description of the selection for errors
+fn select<'a>(
+    mut workspaces: Vec<&'a Workspace>,
+    selector: &WorkspaceSelector,
+    order: WorkspaceOrder,
+) -> Result<(Vec<&'a Workspace>, String), Error> {
+    if selector.active {
+        workspaces.retain(|ws| ws.state.contains(WorkspaceStates::Active));
+        Ok((workspaces, "active workspace".to_string()))
+    } else if let Some(index) = selector.matching.index {
+        workspaces.sort_by(|a, b| order.listed(a, b));
+        let selected = nth(&workspaces, index).copied().into_iter().collect();
+        Ok((selected, format!("workspace with index {index}")))
+    } else if let Some(name) = &selector.matching.name {
+        workspaces.retain(|workspace| workspace.name.as_ref().is_some_and(|n| n == name));
+        Ok((workspaces, format!("workspace with name {name}")))
+    } else if let Some(pattern) = &selector.matching.name_match {
+        let regex = compile(&glob_regex(pattern))?;
+        workspaces.retain(|workspace| workspace.name.as_ref().is_some_and(|n| regex.is_match(n)));
+        Ok((workspaces, format!("workspace with name matching {pattern}")))
+    } else if let Some(pattern) = &selector.matching.name_regex {
+        let regex = compile(pattern)?;
+        workspaces.retain(|workspace| workspace.name.as_ref().is_some_and(|n| regex.is_match(n)));
+        Ok((workspaces, format!("workspace with name matching /{pattern}/")))
+    } else if let Some(protocol_id) = selector.matching.protocol_id {
+        workspaces.retain(|workspace| workspace.id() == protocol_id as u32);
+        Ok((workspaces, format!("workspace with protocol id {protocol_id}")))
+    } else if let Some(coordinates) = &selector.matching.coordinates {
+        if let Some(first) = workspaces.first() {
+            let coords_len = first.coordinates.len();
+            if coords_len != coordinates.len() {
+                return Err(Error::InvalidArgument(format!(
+                    "Wrong coordinate length/number of axis. Expected {coords_len}, got {}",
+                    coordinates.len()
+                )));
+            };
+        }
+        workspaces.retain(|workspace| workspace.coordinates == *coordinates);
+        Ok((workspaces, format!("workspace with coordinates {coordinates:?}")))
+    } else if let Some(id) = &selector.matching.id {
+        workspaces.retain(|workspace| workspace.id.as_ref() == Some(id));
+        Ok((workspaces, format!("workspace with id {id}")))
+    } else {
+        Err(Error::InvalidArgument(
+            "No workspace handle for provided selector found!".to_string(),
+        ))
+    }
+}
+
+// 1-based, negative indices count from the end
+fn nth<T>(items: &[T], index: i32) -> Option<&T> {
+    let position = match index {
+        1.. => index as isize - 1,
+        _ => items.len() as isize + index as isize,
+    };
+    usize::try_from(position).ok().and_then(|position| items.get(position))
+}
+
+// anchored, so the whole name has to match
+fn glob_regex(glob: &str) -> String {
+    let mut regex = String::from("^");
+    for c in glob.chars() {
+        match c {
+            '*' => regex.push_str(".*"),
+            '?' => regex.push('.'),
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    regex.push('$');
+    regex
+}
+
+fn compile(pattern: &str) -> Result<Regex, Error> {
+    Regex::new(pattern)
+        .map_err(|e| Error::InvalidArgument(format!("Invalid name pattern {pattern}: {e}")))
+}
+
+fn filter_matches(filter: &StateFilter, workspace: &Workspace) -> bool {
+    (!filter.urgent || workspace.state.contains(WorkspaceStates::Urgent))
+        && (!filter.hidden || workspace.state.contains(WorkspaceStates::Hidden))
+        && (!filter.inactive || !workspace.state.contains(WorkspaceStates::Active))
+}
+
+fn filter_label(filter: &StateFilter) -> String {
+    [
+        (filter.urgent, "urgent"),
+        (filter.hidden, "hidden"),
+        (filter.inactive, "inactive"),
+    ]
+    .iter()
+    .filter(|(set, _)| *set)
+    .map(|(_, label)| *label)
+    .collect::<Vec<_>>()
+    .join(" ")
+}
+
+fn unique_group(matches: Vec<&WorkspaceGroup>, selection: String) -> Result<&WorkspaceGroup, Error> {
+    match matches.as_slice() {
+        [] => Err(Error::NotFound(format!("Unable to find {selection}!"))),
+        [group] => Ok(group),
+        _ => Err(Error::Ambiguous(format!(
+            "Found {} outputs matching {selection}, narrow the selection",
+            matches.len()
+        ))),
+    }
+}
+
+// selections naming a single workspace must not silently pick one of several matches
+fn unique(matches: Vec<&Workspace>, selection: String) -> Result<&Workspace, Error> {
+    match matches.as_slice() {
+        [] => Err(Error::NotFound(format!("Unable to find {selection}"))),
+        [workspace] => Ok(workspace),
+        _ => Err(Error::Ambiguous(format!(
+            "Found {} workspaces matching {selection}, narrow the selection e.g. with an output or pass --all",
+            matches.len()
+        ))),
     }
 }
 
@@ -322,31 +901,3 @@ impl ProvidesRegistryState for WorkspaceManager {
         OutputState
     }
 }
-
-impl WorkspaceManager {
-    fn list_data(&mut self, args: &ListArgs) -> Result<(), String> {
-        self.workspace_state.sort_workspaces_by_id();
-        self.workspace_state.sort_workspaces_by_coords();
-        self.workspace_state.sort_groups_by_id();
-
-        if let Some(output) = &args.output {
-            let group_filter = self.group_from_output(&output)?.handle.clone();
-            self.workspace_state
-                .workspaces
-                .retain(|ws| ws.group.as_ref().is_some_and(|g| g == &group_filter));
-            self.workspace_state
-                .groups
-                .retain(|g| g.handle == group_filter);
-        };
-
-        if args.json {
-            match serde_json::to_string(&self.workspace_state) {
-                Ok(json) => println!("{json}"),
-                Err(e) => println!("{e}"),
-            };
-        } else {
-            print!("{}", self.workspace_state);
-        }
-        Ok(())
-    }
-}
@@ -3,14 +3,18 @@ use smithay_client_toolkit::globals::GlobalData;
 use wayland_client::{Dispatch, Proxy, WEnum};
 
 use crate::{
+    error::Error,
     ext::workspace::cosmic_v1::client::{
         zcosmic_workspace_group_handle_v1::{self, ZcosmicWorkspaceGroupHandleV1},
         zcosmic_workspace_handle_v1::{self, ZcosmicWorkspaceHandleV1},
         zcosmic_workspace_manager_v1::{Event, ZcosmicWorkspaceManagerV1},
     },
+    workspace_backend::{
+        GroupBackend, GroupHandle, ManagerBackend, WorkspaceBackend, WorkspaceHandle,
+    },
     workspace_state::{
-        GroupCapabilities, GroupHandle, WorkspaceState, WorkspaceCapabilities, WorkspaceDispatch,
-        WorkspaceEvent, WorkspaceHandle, WorkspaceStates,
+        decode_u32_array, GroupCapabilities, TilingState, WorkspaceState, WorkspaceCapabilities,
+        WorkspaceDispatch, WorkspaceEvent, WorkspaceStates,
     },
 };
 
@@ -30,12 +34,15 @@ impl<D: WorkspaceDispatch> Dispatch<ZcosmicWorkspaceManagerV1, GlobalData, D> fo
         );
         let event = match event {
             Event::WorkspaceGroup { workspace_group } => 
-                WorkspaceEvent::WorkspaceGroupCreated(GroupHandle::CosmicV1(workspace_group)),
-            Event::Done {} => {
+                WorkspaceEvent::WorkspaceGroupCreated(GroupHandle::new(workspace_group)),
+            Event::Done => {
                 state.workspace_state_mut().handle_events();
                 return
             }
-            Event::Finished {} => WorkspaceEvent::ManagerFinished,
+            Event::Finished => {
+                state.workspace_state_mut().finish();
+                return
+            }
         };
         state
             .workspace_state_mut()
@@ -61,27 +68,21 @@ impl<D: WorkspaceDispatch> Dispatch<ZcosmicWorkspaceGroupHandleV1, (), D> for Wo
         use zcosmic_workspace_group_handle_v1::Event;
         let event = match event {
             Event::OutputEnter { output } => {
-                WorkspaceEvent::OutputEnter(GroupHandle::CosmicV1(handle.clone()), output)
+                WorkspaceEvent::OutputEnter(GroupHandle::new(handle.clone()), output)
             }
             Event::OutputLeave { output } => {
-                WorkspaceEvent::OutputLeave(GroupHandle::CosmicV1(handle.clone()), output)
+                WorkspaceEvent::OutputLeave(GroupHandle::new(handle.clone()), output)
             }
             Event::Remove => {
-                WorkspaceEvent::WorkspaceGroupRemoved(GroupHandle::CosmicV1(handle.clone()))
-            }
-            Event::Capabilities { capabilities } => {
-                let mut caps = GroupCapabilities::empty();
-                for bits in capabilities {
-                    caps.insert(GroupCapabilities::from_bits_retain(bits as u32));
-                }
-                WorkspaceEvent::WorkspaceGroupCapabilities(
-                    GroupHandle::CosmicV1(handle.clone()),
-                    caps,
-                )
+                WorkspaceEvent::WorkspaceGroupRemoved(GroupHandle::new(handle.clone()))
             }
+            Event::Capabilities { capabilities } => WorkspaceEvent::WorkspaceGroupCapabilities(
+                GroupHandle::new(handle.clone()),
+                group_capabilities(&capabilities),
+            ),
             Event::Workspace { workspace } => WorkspaceEvent::WorkspaceCreated(
-                Some(GroupHandle::CosmicV1(handle.clone())),
-                WorkspaceHandle::CosmicV1(workspace),
+                Some(GroupHandle::new(handle.clone())),
+                WorkspaceHandle::new(workspace),
             ),
         };
         state.workspace_state_mut().events.push(event);
@@ -109,45 +110,28 @@ impl<D: WorkspaceDispatch> Dispatch<ZcosmicWorkspaceHandleV1, (), D> for Workspa
         );
         use zcosmic_workspace_handle_v1::Event;
         let event = match event {
-            Event::State { state } => {
-                if state.len() != 4 {
-                    return;
-                };
-                let bits = u32::from_ne_bytes(state.chunks(4).next().unwrap().try_into().unwrap());
-                WorkspaceEvent::WorkspaceState(
-                    WorkspaceHandle::CosmicV1(handle.clone()),
-                    //WorkspaceStates::from_bits_retain(bits).complement(),
-                    WorkspaceStates::from_bits_retain(bits),
-                )
-            }
+            Event::State { state } => WorkspaceEvent::WorkspaceState(
+                WorkspaceHandle::new(handle.clone()),
+                workspace_states(&state),
+            ),
             Event::Name { name } => {
-                WorkspaceEvent::WorkspaceName(WorkspaceHandle::CosmicV1(handle.clone()), name)
+                WorkspaceEvent::WorkspaceName(WorkspaceHandle::new(handle.clone()), name)
             }
             Event::Coordinates { coordinates } => WorkspaceEvent::WorkspaceCoord(
-                WorkspaceHandle::CosmicV1(handle.clone()),
-                coordinates,
+                WorkspaceHandle::new(handle.clone()),
+                decode_u32_array(&coordinates),
             ),
             Event::Remove => {
-                WorkspaceEvent::WorkspaceRemoved(WorkspaceHandle::CosmicV1(handle.clone()))
-            }
-            Event::Capabilities { capabilities } => {
-                let mut caps = WorkspaceCapabilities::empty();
-                for bits in capabilities.iter() {
-                    let mut bits = bits.clone();
-                    if [8, 16].contains(&bits) {
-                        bits = bits.rotate_left(1);
-                    }
-                    caps.insert(WorkspaceCapabilities::from_bits_retain(bits as u32));
-                }
-                WorkspaceEvent::WorkspaceCapabilities(
-                    WorkspaceHandle::CosmicV1(handle.clone()),
-                    caps,
-                )
+                WorkspaceEvent::WorkspaceRemoved(WorkspaceHandle::new(handle.clone()))
             }
+            Event::Capabilities { capabilities } => WorkspaceEvent::WorkspaceCapabilities(
+                WorkspaceHandle::new(handle.clone()),
+                workspace_capabilities(&capabilities),
+            ),
             Event::TilingState { state } => match state {
                 WEnum::Value(state) => WorkspaceEvent::WorkspaceTilingState(
-                    WorkspaceHandle::CosmicV1(handle.clone()),
-                    state,
+                    WorkspaceHandle::new(handle.clone()),
+                    state.into(),
                 ),
                 WEnum::Unknown(unknown) => {
                     warn!("tiling_state event with unkown value {:?}", unknown);
@@ -159,6 +143,127 @@ impl<D: WorkspaceDispatch> Dispatch<ZcosmicWorkspaceHandleV1, (), D> for Workspa
     }
 }
 
+// state and capabilities are sent as arrays of enum values
+fn workspace_states(array: &[u8]) -> WorkspaceStates {
+    use zcosmic_workspace_handle_v1::State;
+    let mut states = WorkspaceStates::empty();
+    for value in decode_u32_array(array) {
+        match State::try_from(value) {
+            Ok(State::Active) => states.insert(WorkspaceStates::Active),
+            Ok(State::Urgent) => states.insert(WorkspaceStates::Urgent),
+            Ok(State::Hidden) => states.insert(WorkspaceStates::Hidden),
+            Err(()) => warn!("workspace_state event with unknown value: {value}"),
+        }
+    }
+    states
+}
+
+fn workspace_capabilities(array: &[u8]) -> WorkspaceCapabilities {
+    use zcosmic_workspace_handle_v1::ZcosmicWorkspaceCapabilitiesV1 as Capability;
+    let mut caps = WorkspaceCapabilities::empty();
+    for value in decode_u32_array(array) {
+        match Capability::try_from(value) {
+            Ok(Capability::Activate) => caps.insert(WorkspaceCapabilities::Activate),
+            Ok(Capability::Deactivate) => caps.insert(WorkspaceCapabilities::Deactivate),
+            Ok(Capability::Remove) => caps.insert(WorkspaceCapabilities::Remove),
+            Ok(Capability::Rename) => caps.insert(WorkspaceCapabilities::Rename),
+            Ok(Capability::SetTilingState) => caps.insert(WorkspaceCapabilities::SetTilingState),
+            Err(()) => warn!("workspace_capabilities event with unknown value: {value}"),
+        }
+    }
+    caps
+}
+
+impl From<zcosmic_workspace_handle_v1::TilingState> for TilingState {
+    fn from(state: zcosmic_workspace_handle_v1::TilingState) -> Self {
+        match state {
+            zcosmic_workspace_handle_v1::TilingState::FloatingOnly => TilingState::FloatingOnly,
+            zcosmic_workspace_handle_v1::TilingState::TilingEnabled => TilingState::TilingEnabled,
+        }
+    }
+}
+
+impl From<TilingState> for zcosmic_workspace_handle_v1::TilingState {
+    fn from(state: TilingState) -> Self {
+        match state {
+            TilingState::FloatingOnly => zcosmic_workspace_handle_v1::TilingState::FloatingOnly,
+            TilingState::TilingEnabled => zcosmic_workspace_handle_v1::TilingState::TilingEnabled,
+        }
+    }
+}
+
+fn group_capabilities(array: &[u8]) -> GroupCapabilities {
+    use zcosmic_workspace_group_handle_v1::ZcosmicWorkspaceGroupCapabilitiesV1 as Capability;
+    let mut caps = GroupCapabilities::empty();
+    for value in decode_u32_array(array) {
+        match Capability::try_from(value) {
+            Ok(Capability::CreateWorkspace) => caps.insert(GroupCapabilities::CreateWorkspace),
+            Err(()) => warn!("group_capabilities event with unknown value: {value}"),
+        }
+    }
+    caps
+}
+
+impl ManagerBackend for ZcosmicWorkspaceManagerV1 {
+    fn commit(&self) {
+        ZcosmicWorkspaceManagerV1::commit(self)
+    }
+    fn stop(&self) {
+        ZcosmicWorkspaceManagerV1::stop(self)
+    }
+}
+
+impl GroupBackend for ZcosmicWorkspaceGroupHandleV1 {
+    fn protocol_id(&self) -> u32 {
+        self.id().protocol_id()
+    }
+    fn create_workspace(&self, name: String) {
+        ZcosmicWorkspaceGroupHandleV1::create_workspace(self, name)
+    }
+    fn destroy(&self) {
+        ZcosmicWorkspaceGroupHandleV1::destroy(self)
+    }
+}
+
+impl WorkspaceBackend for ZcosmicWorkspaceHandleV1 {
+    fn protocol_id(&self) -> u32 {
+        self.id().protocol_id()
+    }
+    fn activate(&self) {
+        ZcosmicWorkspaceHandleV1::activate(self)
+    }
+    fn deactivate(&self) {
+        ZcosmicWorkspaceHandleV1::deactivate(self)
+    }
+    fn remove(&self) {
+        ZcosmicWorkspaceHandleV1::remove(self)
+    }
+    fn destroy(&self) {
+        ZcosmicWorkspaceHandleV1::destroy(self)
+    }
+    fn rename(&self, name: String) -> Result<(), Error> {
+        require_version(self, 2, "rename")?;
+        ZcosmicWorkspaceHandleV1::rename(self, name);
+        Ok(())
+    }
+    fn set_tiling_state(&self, state: TilingState) -> Result<(), Error> {
+        require_version(self, 2, "set_tiling_state")?;
+        ZcosmicWorkspaceHandleV1::set_tiling_state(self, state.into());
+        Ok(())
+    }
+}
+
+// sending a request the bound version does not know is a protocol error
+fn require_version(handle: &ZcosmicWorkspaceHandleV1, since: u32, request: &str) -> Result<(), Error> {
+    if handle.version() < since {
+        return Err(Error::ProtocolUnsupported(format!(
+            "{request} request requires zcosmic_workspace_handle_v1 version {since}, bound version is {}",
+            handle.version()
+        )));
+    }
+    Ok(())
+}
+
 #[macro_export]
 macro_rules! delegate_workspace_cosmic_v1 {
     ($(@<$( $lt:tt $( : $clt:tt $(+ $dlt:tt )* )? ),+>)? $ty: ty) => {
@@ -173,3 +278,38 @@ macro_rules! delegate_workspace_cosmic_v1 {
         ] => $crate::workspace_state::WorkspaceState);
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::workspace_state::wire_array;
+
+    #[test]
+    fn decode_state() {
+        assert_eq!(workspace_states(&wire_array(&[])), WorkspaceStates::empty());
+        assert_eq!(workspace_states(&wire_array(&[0])), WorkspaceStates::Active);
+        assert_eq!(
+            workspace_states(&wire_array(&[2, 1])),
+            WorkspaceStates::Hidden | WorkspaceStates::Urgent
+        );
+        // unknown values are skipped
+        assert_eq!(workspace_states(&wire_array(&[7, 0])), WorkspaceStates::Active);
+    }
+
+    #[test]
+    fn decode_capabilities() {
+        assert_eq!(
+            workspace_capabilities(&wire_array(&[1, 2, 3])),
+            WorkspaceCapabilities::Activate
+                | WorkspaceCapabilities::Deactivate
+                | WorkspaceCapabilities::Remove
+        );
+        assert_eq!(
+            workspace_capabilities(&wire_array(&[4, 5])),
+            WorkspaceCapabilities::Rename | WorkspaceCapabilities::SetTilingState
+        );
+        assert_eq!(workspace_capabilities(&wire_array(&[0, 9])), WorkspaceCapabilities::empty());
+        assert_eq!(group_capabilities(&wire_array(&[1])), GroupCapabilities::CreateWorkspace);
+        assert_eq!(group_capabilities(&wire_array(&[])), GroupCapabilities::empty());
+    }
+}
@@ -1,26 +1,19 @@
-use log::debug;
+use log::{debug, warn};
 use smithay_client_toolkit::globals::GlobalData;
 use wayland_client::{Dispatch, Proxy};
 
 use crate::{
-    cli::WorkspaceArgs, ext::workspace::{
-        ext_v0::client::{
-            zext_workspace_group_handle_v1::{self, ZextWorkspaceGroupHandleV1},
-            zext_workspace_handle_v1::{self, ZextWorkspaceHandleV1},
-            zext_workspace_manager_v1::{self, ZextWorkspaceManagerV1},
-        },
-        ext_v1::client::ext_workspace_group_handle_v1::ExtWorkspaceGroupHandleV1,
+    ext::workspace::ext_v0::client::{
+        zext_workspace_group_handle_v1::{self, ZextWorkspaceGroupHandleV1},
+        zext_workspace_handle_v1::{self, ZextWorkspaceHandleV1},
+        zext_workspace_manager_v1::{self, ZextWorkspaceManagerV1},
+    }, workspace_backend::{
+        GroupBackend, GroupHandle, ManagerBackend, WorkspaceBackend, WorkspaceHandle,
     }, workspace_state::{
-        GroupHandle, WorkspaceDispatch, WorkspaceEvent, WorkspaceHandle,
-        WorkspaceState, WorkspaceStates,
+        decode_u32_array, WorkspaceDispatch, WorkspaceEvent, WorkspaceState, WorkspaceStates,
     }
 };
 
-#[derive(Clone)]
-struct GroupHandleExtV0 {
-    handle: ExtWorkspaceGroupHandleV1,
-}
-
 impl<D: WorkspaceDispatch> Dispatch<ZextWorkspaceManagerV1, GlobalData, D> for WorkspaceState {
     fn event(
         state: &mut D,
@@ -37,14 +30,16 @@ impl<D: WorkspaceDispatch> Dispatch<ZextWorkspaceManagerV1, GlobalData, D> for W
         );
         let event = match event {
             zext_workspace_manager_v1::Event::WorkspaceGroup { workspace_group } => {
-                WorkspaceEvent::WorkspaceGroupCreated(GroupHandle::ExtV0(workspace_group))
+                WorkspaceEvent::WorkspaceGroupCreated(GroupHandle::new(workspace_group))
             }
-            zext_workspace_manager_v1::Event::Done {} => {
+            zext_workspace_manager_v1::Event::Done => {
                 state.workspace_state_mut().handle_events();
                 return;
             }
-            zext_workspace_manager_v1::Event::Finished {} => 
-                WorkspaceEvent::ManagerFinished
+            zext_workspace_manager_v1::Event::Finished => {
+                state.workspace_state_mut().finish();
+                return;
+            }
         };
         state.workspace_state_mut().events.push(event);
     }
@@ -66,18 +61,18 @@ impl<D: WorkspaceDispatch> Dispatch<ZextWorkspaceGroupHandleV1, (), D> for Works
         debug!("group: {:?}, event: {:?}", handle.id().protocol_id(), event);
         let event = match event {
             zext_workspace_group_handle_v1::Event::OutputEnter { output } => {
-                WorkspaceEvent::OutputEnter(GroupHandle::ExtV0(handle.clone()), output)
+                WorkspaceEvent::OutputEnter(GroupHandle::new(handle.clone()), output)
             }
             zext_workspace_group_handle_v1::Event::OutputLeave { output } => {
-                WorkspaceEvent::OutputLeave(GroupHandle::ExtV0(handle.clone()), output)
+                WorkspaceEvent::OutputLeave(GroupHandle::new(handle.clone()), output)
             }
             zext_workspace_group_handle_v1::Event::Remove => {
-                WorkspaceEvent::WorkspaceGroupRemoved(GroupHandle::ExtV0(handle.clone()))
+                WorkspaceEvent::WorkspaceGroupRemoved(GroupHandle::new(handle.clone()))
             }
             zext_workspace_group_handle_v1::Event::Workspace { workspace } => {
                 WorkspaceEvent::WorkspaceCreated(
-                    Some(GroupHandle::ExtV0(handle.clone())),
-                    WorkspaceHandle::ExtV0(workspace),
+                    Some(GroupHandle::new(handle.clone())),
+                    WorkspaceHandle::new(workspace),
                 )
             }
         };
@@ -103,30 +98,81 @@ impl<D: WorkspaceDispatch> Dispatch<ZextWorkspaceHandleV1, (), D> for WorkspaceS
             event
         );
         let event = match event {
-            zext_workspace_handle_v1::Event::State { state } => {
-                if state.len() != 4 {
-                    return;
-                };
-                let bits = u32::from_ne_bytes(state.chunks(4).next().unwrap().try_into().unwrap());
-                WorkspaceEvent::WorkspaceState(
-                    WorkspaceHandle::ExtV0(handle.clone()),
-                    WorkspaceStates::from_bits_retain(bits), //.symmetric_difference(WorkspaceStates(7)),
-                )
-            }
+            zext_workspace_handle_v1::Event::State { state } => WorkspaceEvent::WorkspaceState(
+                WorkspaceHandle::new(handle.clone()),
+                workspace_states(&state),
+            ),
             zext_workspace_handle_v1::Event::Name { name } => {
-                WorkspaceEvent::WorkspaceName(WorkspaceHandle::ExtV0(handle.clone()), name)
+                WorkspaceEvent::WorkspaceName(WorkspaceHandle::new(handle.clone()), name)
             }
             zext_workspace_handle_v1::Event::Coordinates { coordinates } => {
-                WorkspaceEvent::WorkspaceCoord(WorkspaceHandle::ExtV0(handle.clone()), coordinates)
+                WorkspaceEvent::WorkspaceCoord(
+                    WorkspaceHandle::new(handle.clone()),
+                    decode_u32_array(&coordinates),
+                )
             }
             zext_workspace_handle_v1::Event::Remove => {
-                WorkspaceEvent::WorkspaceRemoved(WorkspaceHandle::ExtV0(handle.clone()))
+                WorkspaceEvent::WorkspaceRemoved(WorkspaceHandle::new(handle.clone()))
             }
         };
         state.workspace_state_mut().events.push(event);
     }
 }
 
+// the state is sent as array of enum values
+fn workspace_states(array: &[u8]) -> WorkspaceStates {
+    use zext_workspace_handle_v1::State;
+    let mut states = WorkspaceStates::empty();
+    for value in decode_u32_array(array) {
+        match State::try_from(value) {
+            Ok(State::Active) => states.insert(WorkspaceStates::Active),
+            Ok(State::Urgent) => states.insert(WorkspaceStates::Urgent),
+            Ok(State::Hidden) => states.insert(WorkspaceStates::Hidden),
+            Err(()) => warn!("workspace_state event with unknown value: {value}"),
+        }
+    }
+    states
+}
+
+impl ManagerBackend for ZextWorkspaceManagerV1 {
+    fn commit(&self) {
+        ZextWorkspaceManagerV1::commit(self)
+    }
+    fn stop(&self) {
+        ZextWorkspaceManagerV1::stop(self)
+    }
+}
+
+impl GroupBackend for ZextWorkspaceGroupHandleV1 {
+    fn protocol_id(&self) -> u32 {
+        self.id().protocol_id()
+    }
+    fn create_workspace(&self, name: String) {
+        ZextWorkspaceGroupHandleV1::create_workspace(self, name)
+    }
+    fn destroy(&self) {
+        ZextWorkspaceGroupHandleV1::destroy(self)
+    }
+}
+
+impl WorkspaceBackend for ZextWorkspaceHandleV1 {
+    fn protocol_id(&self) -> u32 {
+        self.id().protocol_id()
+    }
+    fn activate(&self) {
+        ZextWorkspaceHandleV1::activate(self)
+    }
+    fn deactivate(&self) {
+        ZextWorkspaceHandleV1::deactivate(self)
+    }
+    fn remove(&self) {
+        ZextWorkspaceHandleV1::remove(self)
+    }
+    fn destroy(&self) {
+        ZextWorkspaceHandleV1::destroy(self)
+    }
+}
+
 #[macro_export]
 macro_rules! delegate_workspace_ext_v0 {
     ($(@<$( $lt:tt $( : $clt:tt $(+ $dlt:tt )* )? ),+>)? $ty: ty) => {
@@ -141,3 +187,21 @@ macro_rules! delegate_workspace_ext_v0 {
         ] => $crate::workspace_state::WorkspaceState);
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::workspace_state::wire_array;
+
+    #[test]
+    fn decode_state() {
+        assert_eq!(workspace_states(&wire_array(&[])), WorkspaceStates::empty());
+        assert_eq!(workspace_states(&wire_array(&[0])), WorkspaceStates::Active);
+        assert_eq!(workspace_states(&wire_array(&[1])), WorkspaceStates::Urgent);
+        assert_eq!(
+            workspace_states(&wire_array(&[0, 1, 2])),
+            WorkspaceStates::Active | WorkspaceStates::Urgent | WorkspaceStates::Hidden
+        );
+        assert_eq!(workspace_states(&wire_array(&[3])), WorkspaceStates::empty());
+    }
+}
@@ -3,13 +3,17 @@ use smithay_client_toolkit::globals::GlobalData;
 use wayland_client::{Dispatch, Proxy, WEnum};
 
 use crate::{
+    error::Error,
     ext::workspace::ext_v1::client::{
         ext_workspace_group_handle_v1::{self, ExtWorkspaceGroupHandleV1},
         ext_workspace_handle_v1::{self, ExtWorkspaceHandleV1},
         ext_workspace_manager_v1::{self, ExtWorkspaceManagerV1},
     },
+    workspace_backend::{
+        GroupBackend, GroupHandle, ManagerBackend, WorkspaceBackend, WorkspaceHandle,
+    },
     workspace_state::{
-        GroupCapabilities, GroupHandle, WorkspaceCapabilities, WorkspaceDispatch, WorkspaceEvent, WorkspaceHandle, WorkspaceState, WorkspaceStates
+        decode_u32_array, GroupCapabilities, WorkspaceCapabilities, WorkspaceDispatch, WorkspaceEvent, WorkspaceState, WorkspaceStates
     },
 };
 
@@ -33,16 +37,15 @@ impl<D: WorkspaceDispatch> Dispatch<ExtWorkspaceManagerV1, GlobalData, D> for Wo
                 state
                     .workspace_state_mut()
                     .events
-                    .push(WorkspaceEvent::WorkspaceGroupCreated(GroupHandle::ExtV1(
+                    .push(WorkspaceEvent::WorkspaceGroupCreated(GroupHandle::new(
                         workspace_group,
                     )));
             }
-            Event::Done {} => {
+            Event::Done => {
                 state.workspace_state_mut().handle_events();
-                return
             }
-            Event::Finished {} => {
-                // todo handle event
+            Event::Finished => {
+                state.workspace_state_mut().finish();
             }
             Event::Workspace { workspace } => {
                 state
@@ -50,7 +53,7 @@ impl<D: WorkspaceDispatch> Dispatch<ExtWorkspaceManagerV1, GlobalData, D> for Wo
                     .events
                     .push(WorkspaceEvent::WorkspaceCreated(
                         None,
-                        WorkspaceHandle::ExtV1(workspace),
+                        WorkspaceHandle::new(workspace),
                     ))
             }
         }
@@ -74,20 +77,20 @@ impl<D: WorkspaceDispatch> Dispatch<ExtWorkspaceGroupHandleV1, (), D> for Worksp
         debug!("group: {:?}, event: {:?}", handle.id().protocol_id(), event);
         let event = match event {
             ext_workspace_group_handle_v1::Event::OutputEnter { output } => {
-                WorkspaceEvent::OutputEnter(GroupHandle::ExtV1(handle.clone()), output)
+                WorkspaceEvent::OutputEnter(GroupHandle::new(handle.clone()), output)
             }
             ext_workspace_group_handle_v1::Event::OutputLeave { output } => {
-                WorkspaceEvent::OutputLeave(GroupHandle::ExtV1(handle.clone()), output)
+                WorkspaceEvent::OutputLeave(GroupHandle::new(handle.clone()), output)
             }
             ext_workspace_group_handle_v1::Event::Removed => {
-                WorkspaceEvent::WorkspaceGroupRemoved(GroupHandle::ExtV1(handle.clone()))
+                WorkspaceEvent::WorkspaceGroupRemoved(GroupHandle::new(handle.clone()))
             }
             ext_workspace_group_handle_v1::Event::Capabilities { capabilities } => {
                 match capabilities {
                     WEnum::Value(ext_caps) => {
                         if let Some(caps) = GroupCapabilities::from_bits(ext_caps.bits()) {
                             WorkspaceEvent::WorkspaceGroupCapabilities(
-                                GroupHandle::ExtV1(handle.clone()),
+                                GroupHandle::new(handle.clone()),
                                 caps,
                             )
                         } else {
@@ -103,14 +106,14 @@ impl<D: WorkspaceDispatch> Dispatch<ExtWorkspaceGroupHandleV1, (), D> for Worksp
             }
             ext_workspace_group_handle_v1::Event::WorkspaceEnter { workspace } => {
                 WorkspaceEvent::WorkspaceEnter(
-                    WorkspaceHandle::ExtV1(workspace),
-                    GroupHandle::ExtV1(handle.clone()),
+                    WorkspaceHandle::new(workspace),
+                    GroupHandle::new(handle.clone()),
                 )
             }
             ext_workspace_group_handle_v1::Event::WorkspaceLeave { workspace } => {
                 WorkspaceEvent::WorkspaceLeave(
-                    WorkspaceHandle::ExtV1(workspace),
-                    GroupHandle::ExtV1(handle.clone()),
+                    WorkspaceHandle::new(workspace),
+                    GroupHandle::new(handle.clone()),
                 )
             }
         };
@@ -134,12 +137,12 @@ impl<D: WorkspaceDispatch> Dispatch<ExtWorkspaceHandleV1, (), D> for WorkspaceSt
         );
         let event = match event {
             ext_workspace_handle_v1::Event::Id { id } => {
-                WorkspaceEvent::WorkspaceId(WorkspaceHandle::ExtV1(handle.clone()), id)
+                WorkspaceEvent::WorkspaceId(WorkspaceHandle::new(handle.clone()), id)
             },
             ext_workspace_handle_v1::Event::State { state } => match state {
-                WEnum::Value(s) => WorkspaceEvent::WorkspaceState(
-                    WorkspaceHandle::ExtV1(handle.clone()),
-                    WorkspaceStates::from_bits_retain(s.bits()),
+                WEnum::Value(state) => WorkspaceEvent::WorkspaceState(
+                    WorkspaceHandle::new(handle.clone()),
+                    workspace_states(state),
                 ),
                 WEnum::Unknown(unknown) => {
                     warn!("workspace_state event with unknown value: {unknown}");
@@ -147,19 +150,22 @@ impl<D: WorkspaceDispatch> Dispatch<ExtWorkspaceHandleV1, (), D> for WorkspaceSt
                 }
             },
             ext_workspace_handle_v1::Event::Name { name } => {
-                WorkspaceEvent::WorkspaceName(WorkspaceHandle::ExtV1(handle.clone()), name)
+                WorkspaceEvent::WorkspaceName(WorkspaceHandle::new(handle.clone()), name)
             }
             ext_workspace_handle_v1::Event::Coordinates { coordinates } => {
-                WorkspaceEvent::WorkspaceCoord(WorkspaceHandle::ExtV1(handle.clone()), coordinates)
+                WorkspaceEvent::WorkspaceCoord(
+                    WorkspaceHandle::new(handle.clone()),
+                    decode_u32_array(&coordinates),
+                )
             }
             ext_workspace_handle_v1::Event::Removed => {
-                WorkspaceEvent::WorkspaceRemoved(WorkspaceHandle::ExtV1(handle.clone()))
+                WorkspaceEvent::WorkspaceRemoved(WorkspaceHandle::new(handle.clone()))
             }
             ext_workspace_handle_v1::Event::Capabilities { capabilities } => match capabilities {
                 WEnum::Value(ext_caps) => {
                     if let Some(caps) = WorkspaceCapabilities::from_bits(ext_caps.bits()) {
                         WorkspaceEvent::WorkspaceCapabilities(
-                            WorkspaceHandle::ExtV1(handle.clone()),
+                            WorkspaceHandle::new(handle.clone()),
                             caps,
                         )
                     } else {
@@ -177,6 +183,69 @@ impl<D: WorkspaceDispatch> Dispatch<ExtWorkspaceHandleV1, (), D> for WorkspaceSt
     }
 }
 
+// the bit values of the state bitfield differ from WorkspaceStates
+fn workspace_states(state: ext_workspace_handle_v1::State) -> WorkspaceStates {
+    use ext_workspace_handle_v1::State;
+    let mut states = WorkspaceStates::empty();
+    states.set(WorkspaceStates::Active, state.contains(State::Active));
+    states.set(WorkspaceStates::Urgent, state.contains(State::Urgent));
+    states.set(WorkspaceStates::Hidden, state.contains(State::Hidden));
+    if !State::all().contains(state) {
+        warn!("workspace_state event with unknown bits: {state:?}");
+    }
+    states
+}
+
+impl ManagerBackend for ExtWorkspaceManagerV1 {
+    fn commit(&self) {
+        ExtWorkspaceManagerV1::commit(self)
+    }
+    fn stop(&self) {
+        ExtWorkspaceManagerV1::stop(self)
+    }
+}
+
+impl GroupBackend for ExtWorkspaceGroupHandleV1 {
+    fn protocol_id(&self) -> u32 {
+        self.id().protocol_id()
+    }
+    fn create_workspace(&self, name: String) {
+        ExtWorkspaceGroupHandleV1::create_workspace(self, name)
+    }
+    fn destroy(&self) {
+        ExtWorkspaceGroupHandleV1::destroy(self)
+    }
+}
+
+impl WorkspaceBackend for ExtWorkspaceHandleV1 {
+    fn protocol_id(&self) -> u32 {
+        self.id().protocol_id()
+    }
+    fn activate(&self) {
+        ExtWorkspaceHandleV1::activate(self)
+    }
+    fn deactivate(&self) {
+        ExtWorkspaceHandleV1::deactivate(self)
+    }
+    fn remove(&self) {
+        ExtWorkspaceHandleV1::remove(self)
+    }
+    fn destroy(&self) {
+        ExtWorkspaceHandleV1::destroy(self)
+    }
+    fn assign(&self, group: &GroupHandle) -> Result<(), Error> {
+        match group.downcast_ref::<ExtWorkspaceGroupHandleV1>() {
+            Some(group) => {
+                ExtWorkspaceHandleV1::assign(self, group);
+                Ok(())
+            }
+            None => Err(Error::ProtocolUnsupported(
+                "assign request workspace and group handle version mismatch".to_string(),
+            )),
+        }
+    }
+}
+
 #[macro_export]
 macro_rules! delegate_workspace_ext_v1 {
     ($(@<$( $lt:tt $( : $clt:tt $(+ $dlt:tt )* )? ),+>)? $ty: ty) => {
@@ -191,3 +260,21 @@ macro_rules! delegate_workspace_ext_v1 {
         ] => $crate::workspace_state::WorkspaceState);
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ext_workspace_handle_v1::State;
+
+    #[test]
+    fn decode_state() {
+        assert_eq!(workspace_states(State::empty()), WorkspaceStates::empty());
+        assert_eq!(workspace_states(State::Active), WorkspaceStates::Active);
+        assert_eq!(workspace_states(State::Urgent), WorkspaceStates::Urgent);
+        assert_eq!(workspace_states(State::Hidden), WorkspaceStates::Hidden);
+        assert_eq!(
+            workspace_states(State::from_bits_retain(0b1011)),
+            WorkspaceStates::Active | WorkspaceStates::Urgent
+        );
+    }
+}
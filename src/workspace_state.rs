@@ -1,9 +1,10 @@
-use std::{cmp::Ordering, fmt::Display};
+use std::cmp::Ordering;
+use std::fmt::Display;
 
-use log::{debug, info, warn};
+use log::{debug, warn};
 use serde::{
     ser::{SerializeSeq, SerializeStruct},
-    Deserialize, Serialize, Serializer,
+    Serialize, Serializer,
 };
 use smithay_client_toolkit::{
     output::{OutputData, OutputInfo},
@@ -12,21 +13,23 @@ use smithay_client_toolkit::{
 
 use bitflags::bitflags;
 
+use crate::error::Error;
+use crate::workspace_backend::{GroupHandle, ManagerHandle, WorkspaceHandle};
 use crate::ext::workspace::{
         cosmic_v1::client::{
-            zcosmic_workspace_group_handle_v1::{self, ZcosmicWorkspaceGroupHandleV1},
-            zcosmic_workspace_handle_v1::{self, TilingState, ZcosmicWorkspaceHandleV1},
-            zcosmic_workspace_manager_v1::{self, ZcosmicWorkspaceManagerV1},
+            zcosmic_workspace_group_handle_v1::ZcosmicWorkspaceGroupHandleV1,
+            zcosmic_workspace_handle_v1::ZcosmicWorkspaceHandleV1,
+            zcosmic_workspace_manager_v1::ZcosmicWorkspaceManagerV1,
         },
         ext_v0::client::{
-            zext_workspace_group_handle_v1::{self, ZextWorkspaceGroupHandleV1},
-            zext_workspace_handle_v1::{self, ZextWorkspaceHandleV1},
-            zext_workspace_manager_v1::{self, ZextWorkspaceManagerV1},
+            zext_workspace_group_handle_v1::ZextWorkspaceGroupHandleV1,
+            zext_workspace_handle_v1::ZextWorkspaceHandleV1,
+            zext_workspace_manager_v1::ZextWorkspaceManagerV1,
         },
         ext_v1::client::{
-            ext_workspace_group_handle_v1::{self, ExtWorkspaceGroupHandleV1},
-            ext_workspace_handle_v1::{self, ExtWorkspaceHandleV1},
-            ext_workspace_manager_v1::{self, ExtWorkspaceManagerV1},
+            ext_workspace_group_handle_v1::ExtWorkspaceGroupHandleV1,
+            ext_workspace_handle_v1::ExtWorkspaceHandleV1,
+            ext_workspace_manager_v1::ExtWorkspaceManagerV1,
         },
 };
 
@@ -40,7 +43,7 @@ pub enum Protocol {
     CosmicV1,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub struct GroupCapabilities(u32);
 
 bitflags! {
@@ -49,7 +52,7 @@ bitflags! {
     }
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub struct WorkspaceCapabilities(u32);
 
 bitflags! {
@@ -63,7 +66,7 @@ bitflags! {
     }
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
 pub struct WorkspaceStates(u32);
 
 bitflags! {
@@ -74,24 +77,11 @@ bitflags! {
     }
 }
 
-pub enum ManagerHandle {
-    ExtV0(ZextWorkspaceManagerV1),
-    ExtV1(ExtWorkspaceManagerV1),
-    CosmicV1(ZcosmicWorkspaceManagerV1),
-}
-
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub enum GroupHandle {
-    ExtV0(ZextWorkspaceGroupHandleV1),
-    ExtV1(ExtWorkspaceGroupHandleV1),
-    CosmicV1(ZcosmicWorkspaceGroupHandleV1),
-}
-
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub enum WorkspaceHandle {
-    ExtV0(ZextWorkspaceHandleV1),
-    ExtV1(ExtWorkspaceHandleV1),
-    CosmicV1(ZcosmicWorkspaceHandleV1),
+/// Tiling state of a workspace. Only sent by cosmic-v1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, clap::ValueEnum)]
+pub enum TilingState {
+    FloatingOnly,
+    TilingEnabled,
 }
 
 #[derive(Debug, Clone)]
@@ -101,13 +91,27 @@ pub struct WorkspaceGroup {
     pub capabilities: GroupCapabilities,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutputArea {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl OutputArea {
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        (self.x..self.x + self.width).contains(&x) && (self.y..self.y + self.height).contains(&y)
+    }
+}
+
 #[derive(Clone, Debug, Serialize)]
 pub struct Workspace {
     #[serde(skip_serializing)]
     pub handle: WorkspaceHandle,
     pub name: Option<String>,
     pub id: Option<String>,
-    pub coordinates: Vec<u8>,
+    pub coordinates: Vec<u32>,
     pub state: WorkspaceStates,
     #[serde(skip_serializing)]
     pub group: Option<GroupHandle>,
@@ -126,76 +130,87 @@ impl WorkspaceGroup {
     pub fn get_output_name(&self) -> Option<String> {
         self.output.as_ref().and_then(|o| {
             o.data::<OutputData>().and_then(|data| {
-                data.with_output_info(|info| info.name.as_ref().and_then(|name| Some(name.clone())))
+                data.with_output_info(|info| info.name.clone())
             })
         })
     }
 
+    /// Position and size of the output in the compositor space. Without xdg-output the position
+    /// from the wl_output geometry and the size of the current mode.
+    pub fn get_output_area(&self) -> Option<OutputArea> {
+        let info = self.get_output_info()?;
+        let (x, y) = info.logical_position.unwrap_or(info.location);
+        let (width, height) = info.logical_size.or_else(|| {
+            info.modes
+                .iter()
+                .find(|mode| mode.current)
+                .map(|mode| mode.dimensions)
+        })?;
+        Some(OutputArea {
+            x,
+            y,
+            width,
+            height,
+        })
+    }
+
     pub fn id(&self) -> u32 {
-        match &self.handle {
-            GroupHandle::ExtV1(handle) => handle.id().protocol_id(),
-            GroupHandle::ExtV0(handle) => handle.id().protocol_id(),
-            GroupHandle::CosmicV1(handle) => handle.id().protocol_id(),
-        }
+        self.handle.protocol_id()
     }
     pub fn create_workspace(&self, name: String) {
-        match &self.handle {
-            GroupHandle::ExtV0(handle) => handle.create_workspace(name),
-            GroupHandle::ExtV1(handle) => handle.create_workspace(name),
-            GroupHandle::CosmicV1(handle) => handle.create_workspace(name),
-        }
+        self.handle.create_workspace(name)
     }
 }
 impl Workspace {
     pub fn id(&self) -> u32 {
-        match &self.handle {
-            WorkspaceHandle::ExtV1(handle) => handle.id().protocol_id(),
-            WorkspaceHandle::ExtV0(handle) => handle.id().protocol_id(),
-            WorkspaceHandle::CosmicV1(handle) => handle.id().protocol_id(),
-        }
+        self.handle.protocol_id()
     }
     pub fn activate(&self) {
-        match &self.handle {
-            WorkspaceHandle::ExtV0(handle) => handle.activate(),
-            WorkspaceHandle::ExtV1(handle) => handle.activate(),
-            WorkspaceHandle::CosmicV1(handle) => handle.activate(),
-        }
+        self.handle.activate()
     }
     pub fn deactivate(&self) {
-        match &self.handle {
-            WorkspaceHandle::ExtV0(handle) => handle.deactivate(),
-            WorkspaceHandle::ExtV1(handle) => handle.deactivate(),
-            WorkspaceHandle::CosmicV1(handle) => handle.deactivate(),
-        }
+        self.handle.deactivate()
     }
     pub fn destroy(&self) {
-        match &self.handle {
-            WorkspaceHandle::ExtV0(handle) => handle.destroy(),
-            WorkspaceHandle::ExtV1(handle) => handle.destroy(),
-            WorkspaceHandle::CosmicV1(handle) => handle.destroy(),
-        }
+        self.handle.destroy()
     }
     pub fn remove(&self) {
-        match &self.handle {
-            WorkspaceHandle::ExtV0(handle) => handle.remove(),
-            WorkspaceHandle::ExtV1(handle) => handle.remove(),
-            WorkspaceHandle::CosmicV1(handle) => handle.remove(),
-        }
+        self.handle.remove()
     }
     // todo change to group instead of handle
-    pub fn assign(&self, group: &GroupHandle) -> Result<(), String> {
-        match &self.handle {
-            WorkspaceHandle::ExtV1(handle) => match group {
-                GroupHandle::ExtV1(group_handle) => {
-                    handle.assign(group_handle);
-                    Ok(())
-                }
-                _ => Err(format!(
-                    "assign request workspace and group handle version mismatch"
-                )),
-            },
-            _ => Err(format!("assign request not supported by used protocol")),
+    pub fn assign(&self, group: &GroupHandle) -> Result<(), Error> {
+        self.check_assign()?;
+        self.handle.assign(group)
+    }
+    /// Fails like [`Workspace::assign`] without sending the request.
+    pub fn check_assign(&self) -> Result<(), Error> {
+        if !self.capabilities.contains(WorkspaceCapabilities::Assign) {
+            return Err(Error::CapabilityMissing(
+                "Workspace does not advertise the assign capability".to_string(),
+            ));
+        }
+        Ok(())
+    }
+    pub fn rename(&self, name: String) -> Result<(), Error> {
+        if !self.capabilities.contains(WorkspaceCapabilities::Rename) {
+            return Err(Error::CapabilityMissing(
+                "Workspace does not advertise the rename capability".to_string(),
+            ));
+        }
+        self.handle.rename(name)
+    }
+    pub fn set_tiling_state(&self, state: TilingState) -> Result<(), Error> {
+        self.check_set_tiling_state()?;
+        self.handle.set_tiling_state(state)
+    }
+    /// Fails like [`Workspace::set_tiling_state`] without sending the request.
+    pub fn check_set_tiling_state(&self) -> Result<(), Error> {
+        if !self.capabilities.contains(WorkspaceCapabilities::SetTilingState) {
+            return Err(Error::CapabilityMissing(
+                "Workspace does not advertise the set_tiling_state capability".to_string(),
+            ));
         }
+        Ok(())
     }
 }
 
@@ -205,118 +220,232 @@ pub struct WorkspaceState {
     pub manager: ManagerHandle,
     pub events: Vec<WorkspaceEvent>,
     pub protocol: Protocol,
+    /// Set once the compositor sent the manager's `finished` event. The manager object is gone
+    /// and the compositor will neither send events nor accept requests anymore.
+    pub finished: bool,
+    /// Applied batches with the groups and workspaces before and after them. Only recorded while
+    /// `Some`, for consumers like `watch` that react to changes.
+    pub batches: Option<Vec<Batch>>,
+    /// Order of workspaces in `list`, for `--index` and for relative navigation.
+    pub order: WorkspaceOrder,
+}
+
+/// Groups and workspaces at one point in time.
+#[derive(Debug, Clone, Default)]
+pub struct Checkpoint {
+    pub groups: Vec<WorkspaceGroup>,
+    pub workspaces: Vec<Workspace>,
+}
+
+/// Events of one `done`, applied together.
+#[derive(Debug, Clone)]
+pub struct Batch {
+    pub before: Checkpoint,
+    pub after: Checkpoint,
 }
 
 impl WorkspaceState {
     pub fn commit(&self) {
-        match &self.manager {
-            ManagerHandle::ExtV0(manager) => manager.commit(),
-            ManagerHandle::ExtV1(manager) => manager.commit(),
-            ManagerHandle::CosmicV1(manager) => manager.commit(),
-        }
+        self.manager.commit()
     }
-    pub fn get_workspace_by_handle(&mut self, handle: &WorkspaceHandle) -> &mut Workspace {
-        match self.workspaces.iter_mut().find(|ws| &ws.handle == handle) {
-            Some(workspace) => workspace,
-            None => panic!("no workspace found for handle {handle:?}"),
+    /// `finished` is a destructor and not followed by `done`, events buffered until then are
+    /// never completed and get dropped.
+    pub fn finish(&mut self) {
+        if !self.events.is_empty() {
+            debug!("dropping {} incomplete events of finished manager", self.events.len());
         }
+        self.events.clear();
+        self.finished = true;
     }
-    pub fn get_group_by_handle(&mut self, handle: &GroupHandle) -> &mut WorkspaceGroup {
-        match self.groups.iter_mut().find(|group| &group.handle == handle) {
-            Some(group) => group,
-            None => panic!("no group found for handle {handle:?}"),
-        }
+    pub fn get_workspace_by_handle(&mut self, handle: &WorkspaceHandle) -> Option<&mut Workspace> {
+        self.workspaces.iter_mut().find(|ws| &ws.handle == handle)
     }
+    pub fn get_group_by_handle(&mut self, handle: &GroupHandle) -> Option<&mut WorkspaceGroup> {
+        self.groups.iter_mut().find(|group| &group.handle == handle)
+    }
+    /// Sorts workspaces in the order `list` prints them.
+    pub fn sort_workspaces(&mut self) {
+        let order = self.order;
+        self.workspaces.sort_by(|a, b| order.listed(a, b));
+    }
+
     pub fn sort_workspaces_by_coords(&mut self) {
-        self.workspaces.sort_unstable_by(|a, b| {
-            (0..a.coordinates.len()).find_map(|i| {
-                if a.coordinates[i] > b.coordinates[i] { Some(Ordering::Greater) }
-                else if a.coordinates[i] < b.coordinates[i] { Some(Ordering::Less) }
-                else { None }
-            }).map_or(Ordering::Equal, |o| o)
-        });
+        self.workspaces.sort_by(|a, b| a.coordinates.cmp(&b.coordinates));
     }
 
     pub fn sort_workspaces_by_id(&mut self) {
-        self.workspaces.sort_unstable_by(|a, b| a.id().cmp(&b.id()));
+        self.workspaces.sort_unstable_by_key(|ws| ws.id());
     }
 
     pub fn sort_groups_by_id(&mut self) {
-        self.groups.sort_unstable_by(|a, b| a.id().cmp(&b.id()));
+        self.groups.sort_unstable_by_key(|group| group.id());
     }
 
+    /// Applies the events buffered since the last `done` as one batch. Objects created in the
+    /// batch are added before and objects removed in the batch are dropped after all other events,
+    /// so the order of events inside a batch does not matter. Events referring to unknown handles
+    /// are logged and skipped.
     pub fn handle_events(&mut self) {
-        for event in self.events.clone().into_iter() {
-            match event {
-                WorkspaceEvent::WorkspaceGroupCreated(group_handle) => {
-                    self.groups.push(WorkspaceGroup {
-                        handle: group_handle,
-                        output: None,
-                        capabilities: GroupCapabilities::empty(),
-                    });
+        let before = self.batches.is_some().then(|| self.checkpoint());
+        let mut events = std::mem::take(&mut self.events);
+        events.sort_by_key(|event| match event {
+            WorkspaceEvent::WorkspaceGroupCreated(_) => 0,
+            WorkspaceEvent::WorkspaceCreated(_, _) => 1,
+            WorkspaceEvent::WorkspaceGroupRemoved(_) | WorkspaceEvent::WorkspaceRemoved(_) => 3,
+            _ => 2,
+        });
+        for event in events {
+            self.handle_event(event);
+        }
+        if let Some(before) = before {
+            let after = self.checkpoint();
+            if let Some(batches) = self.batches.as_mut() {
+                batches.push(Batch { before, after });
+            }
+        }
+    }
+
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            groups: self.groups.clone(),
+            workspaces: self.workspaces.clone(),
+        }
+    }
+
+    fn handle_event(&mut self, event: WorkspaceEvent) {
+        match event {
+            WorkspaceEvent::WorkspaceGroupCreated(group_handle) => {
+                if self.groups.iter().any(|group| group.handle == group_handle) {
+                    warn!("group {group_handle:?} announced twice, skipping");
+                    return;
                 }
-                WorkspaceEvent::WorkspaceGroupRemoved(group_handle) => {
-                    self.groups.retain(|group| group.handle != group_handle);
+                self.groups.push(WorkspaceGroup {
+                    handle: group_handle,
+                    output: None,
+                    capabilities: GroupCapabilities::empty(),
+                });
+            }
+            WorkspaceEvent::WorkspaceGroupRemoved(group_handle) => {
+                if self.known_group(&group_handle).is_none() {
+                    return;
                 }
-                WorkspaceEvent::WorkspaceCreated(group_handle, workspace_handle) => {
-                    self.workspaces.push(Workspace {
-                        handle: workspace_handle,
-                        id: None,
-                        name: None,
-                        coordinates: Vec::new(),
-                        state: WorkspaceStates::empty(),
-                        group: group_handle,
-                        tiling_state: None,
-                        capabilities: WorkspaceCapabilities::empty(),
-                    })
+                self.groups.retain(|group| group.handle != group_handle);
+                for workspace in self.workspaces.iter_mut() {
+                    if workspace.group.as_ref() == Some(&group_handle) {
+                        workspace.group = None;
+                    }
+                }
+                group_handle.destroy();
+            }
+            WorkspaceEvent::WorkspaceCreated(group_handle, workspace_handle) => {
+                if self.workspaces.iter().any(|ws| ws.handle == workspace_handle) {
+                    warn!("workspace {workspace_handle:?} announced twice, skipping");
+                    return;
                 }
-                WorkspaceEvent::WorkspaceRemoved(workspace_handle) => {
+                let group = group_handle.filter(|g| self.known_group(g).is_some());
+                self.workspaces.push(Workspace {
+                    handle: workspace_handle,
+                    id: None,
+                    name: None,
+                    coordinates: Vec::new(),
+                    state: WorkspaceStates::empty(),
+                    group,
+                    tiling_state: None,
+                    capabilities: WorkspaceCapabilities::empty(),
+                })
+            }
+            WorkspaceEvent::WorkspaceRemoved(workspace_handle) => {
+                // the handle is inert after the remove event and only waits to be destroyed
+                if self.known_workspace(&workspace_handle).is_some() {
                     self.workspaces
                         .retain(|workspace| workspace.handle != workspace_handle);
+                    workspace_handle.destroy();
+                }
+            }
+            WorkspaceEvent::OutputEnter(group_handle, output) => {
+                if let Some(group) = self.known_group(&group_handle) {
+                    group.output = Some(output);
                 }
-                WorkspaceEvent::OutputEnter(group_handle, output) => {
-                    self.get_group_by_handle(&group_handle).output = Some(output);
+            }
+            WorkspaceEvent::OutputLeave(group_handle, output) => {
+                if let Some(group) = self.known_group(&group_handle) {
+                    if group.output.as_ref() == Some(&output) {
+                        group.output = None;
+                    } else {
+                        warn!("output_leave event for output the group did not enter");
+                    }
                 }
-                WorkspaceEvent::OutputLeave(group_handle, output) => {
-                    self.get_group_by_handle(&group_handle).output = None
+            }
+            WorkspaceEvent::WorkspaceState(workspace_handle, state) => {
+                if let Some(workspace) = self.known_workspace(&workspace_handle) {
+                    workspace.state = state;
                 }
-                WorkspaceEvent::WorkspaceState(workspace_handle, state) => {
-                    self.get_workspace_by_handle(&workspace_handle).state = state;
+            }
+            WorkspaceEvent::WorkspaceId(workspace_handle, id) => {
+                if let Some(workspace) = self.known_workspace(&workspace_handle) {
+                    workspace.id = Some(id);
                 }
-                WorkspaceEvent::WorkspaceId(workspace_handle, id) => {
-                    self.get_workspace_by_handle(&workspace_handle).id = Some(id);
+            }
+            WorkspaceEvent::WorkspaceName(workspace_handle, name) => {
+                if let Some(workspace) = self.known_workspace(&workspace_handle) {
+                    workspace.name = Some(name);
                 }
-                WorkspaceEvent::WorkspaceName(workspace_handle, name) => {
-                    self.get_workspace_by_handle(&workspace_handle).name = Some(name);
+            }
+            WorkspaceEvent::WorkspaceCoord(workspace_handle, coordinates) => {
+                if let Some(workspace) = self.known_workspace(&workspace_handle) {
+                    workspace.coordinates = coordinates;
                 }
-                WorkspaceEvent::WorkspaceCoord(workspace_handle, coordinates) => {
-                    self.get_workspace_by_handle(&workspace_handle).coordinates = coordinates;
+            }
+            WorkspaceEvent::WorkspaceGroupCapabilities(group_handle, caps) => {
+                if let Some(group) = self.known_group(&group_handle) {
+                    group.capabilities = caps;
                 }
-                WorkspaceEvent::WorkspaceGroupCapabilities(group_handle, caps) => {
-                    self.get_group_by_handle(&group_handle).capabilities = caps;
+            }
+            WorkspaceEvent::WorkspaceEnter(workspace_handle, group_handle) => {
+                if self.known_group(&group_handle).is_none() {
+                    return;
                 }
-                WorkspaceEvent::WorkspaceEnter(workspace_handle, group_handle) => {
-                    self.get_workspace_by_handle(&workspace_handle).group = Some(group_handle);
+                if let Some(workspace) = self.known_workspace(&workspace_handle) {
+                    workspace.group = Some(group_handle);
                 }
-                WorkspaceEvent::WorkspaceLeave(workspace_handle, group_handle) => {
-                    let workspace = self.get_workspace_by_handle(&workspace_handle);
+            }
+            WorkspaceEvent::WorkspaceLeave(workspace_handle, group_handle) => {
+                if let Some(workspace) = self.known_workspace(&workspace_handle) {
                     if workspace.group.as_ref().is_some_and(|g| g == &group_handle) {
                         workspace.group = None;
                     } else {
                         warn!("workspace_leave event with wrong group handle");
                     }
                 }
-                WorkspaceEvent::WorkspaceCapabilities(workspace_handle, caps) => {
-                    self.get_workspace_by_handle(&workspace_handle).capabilities = caps;
+            }
+            WorkspaceEvent::WorkspaceCapabilities(workspace_handle, caps) => {
+                if let Some(workspace) = self.known_workspace(&workspace_handle) {
+                    workspace.capabilities = caps;
                 }
-                WorkspaceEvent::WorkspaceTilingState(workspace_handle, tiling_state) => {
-                    self.get_workspace_by_handle(&workspace_handle).tiling_state =
-                        Some(tiling_state);
+            }
+            WorkspaceEvent::WorkspaceTilingState(workspace_handle, tiling_state) => {
+                if let Some(workspace) = self.known_workspace(&workspace_handle) {
+                    workspace.tiling_state = Some(tiling_state);
                 }
-                WorkspaceEvent::ManagerFinished => todo!(),
             }
         }
     }
+
+    fn known_workspace(&mut self, handle: &WorkspaceHandle) -> Option<&mut Workspace> {
+        let workspace = self.get_workspace_by_handle(handle);
+        if workspace.is_none() {
+            warn!("event for unknown workspace {handle:?}, skipping");
+        }
+        workspace
+    }
+
+    fn known_group(&mut self, handle: &GroupHandle) -> Option<&mut WorkspaceGroup> {
+        let group = self.get_group_by_handle(handle);
+        if group.is_none() {
+            warn!("event for unknown group {handle:?}, skipping");
+        }
+        group
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -332,11 +461,75 @@ pub enum WorkspaceEvent {
     WorkspaceRemoved(WorkspaceHandle),
     WorkspaceState(WorkspaceHandle, WorkspaceStates),
     WorkspaceCapabilities(WorkspaceHandle, WorkspaceCapabilities),
-    WorkspaceCoord(WorkspaceHandle, Vec<u8>),
+    WorkspaceCoord(WorkspaceHandle, Vec<u32>),
     WorkspaceName(WorkspaceHandle, String),
     WorkspaceId(WorkspaceHandle, String),
     WorkspaceTilingState(WorkspaceHandle, TilingState),
-    ManagerFinished,
+}
+
+/// Name of the output of the group, if the group is known and has an output.
+pub fn output_name(groups: &[WorkspaceGroup], group: &GroupHandle) -> Option<String> {
+    groups
+        .iter()
+        .find(|g| &g.handle == group)
+        .and_then(|g| g.get_output_name())
+}
+
+/// By coordinates, then by protocol id. The default [`WorkspaceOrder`].
+pub fn list_order(a: &Workspace, b: &Workspace) -> Ordering {
+    a.coordinates.cmp(&b.coordinates).then(a.id().cmp(&b.id()))
+}
+
+/// Order of workspaces in `list`, for `--index` and for relative navigation.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum WorkspaceOrder {
+    /// By coordinates, then by protocol id.
+    #[default]
+    Coordinates,
+    ProtocolId,
+    /// By name, then by protocol id. Workspaces without name come last.
+    Name,
+    /// In the order the compositor created them.
+    Creation,
+}
+
+impl WorkspaceOrder {
+    /// Order of workspaces in the same group.
+    pub fn compare(self, a: &Workspace, b: &Workspace) -> Ordering {
+        match self {
+            WorkspaceOrder::Coordinates => list_order(a, b),
+            WorkspaceOrder::ProtocolId => a.id().cmp(&b.id()),
+            WorkspaceOrder::Name => (a.name.is_none(), &a.name)
+                .cmp(&(b.name.is_none(), &b.name))
+                .then(a.id().cmp(&b.id())),
+            // workspaces are kept in the order they were created and only sorted stably
+            WorkspaceOrder::Creation => Ordering::Equal,
+        }
+    }
+
+    /// Order of all workspaces as `list` prints them: by the protocol id of their group,
+    /// workspaces without group last, then in this order.
+    pub fn listed(self, a: &Workspace, b: &Workspace) -> Ordering {
+        let group = |ws: &Workspace| (ws.group.is_none(), ws.group.as_ref().map(|g| g.protocol_id()));
+        group(a).cmp(&group(b)).then(self.compare(a, b))
+    }
+}
+
+/// Decodes a wayland `array` argument holding u32 values. Arrays are sent in host byte order.
+pub fn decode_u32_array(array: &[u8]) -> Vec<u32> {
+    let chunks = array.chunks_exact(4);
+    if !chunks.remainder().is_empty() {
+        warn!("array length {} is not a multiple of 4, ignoring trailing bytes", array.len());
+    }
+    chunks
+        .map(|chunk| u32::from_ne_bytes(chunk.try_into().unwrap()))
+        .collect()
+}
+
+/// Encodes u32 values like the compositor sends them in a wayland `array` argument.
+#[cfg(test)]
+pub(crate) fn wire_array(values: &[u32]) -> Vec<u8> {
+    values.iter().flat_map(|v| v.to_ne_bytes()).collect()
 }
 
 pub trait WorkspaceHandler {
@@ -379,45 +572,63 @@ impl Serialize for WorkspaceState {
     where
         S: Serializer,
     {
-        let mut state = serializer.serialize_seq(Some(self.groups.len()))?;
+        serialize_groups(&self.groups, &self.workspaces, serializer)
+    }
+}
 
-        #[derive(Serialize)]
-        struct GroupSerialize {
-            #[serde(serialize_with = "serialize_wloutput")]
-            output: Option<WlOutput>,
-            workspaces: Vec<Workspace>,
-        }
-        for group in self.groups.iter() {
-            let workspaces = self
-                .workspaces
-                .iter()
-                .filter(|ws| ws.group.clone().is_some_and(|g| g == group.handle))
-                .cloned()
-                .collect::<Vec<_>>();
-            if !workspaces.is_empty() {
-                let group_s = GroupSerialize {
-                    output: group.output.clone(),
-                    workspaces: workspaces,
-                };
-                state.serialize_element(&group_s)?;
-            }
-        }
+impl Serialize for Checkpoint {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serialize_groups(&self.groups, &self.workspaces, serializer)
+    }
+}
 
-        // unassigned workspaces
-        let unassigned_workspaces = self
-            .workspaces
+fn serialize_groups<S>(
+    groups: &[WorkspaceGroup],
+    workspaces: &[Workspace],
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    let mut state = serializer.serialize_seq(Some(groups.len()))?;
+
+    #[derive(Serialize)]
+    struct GroupSerialize {
+        #[serde(serialize_with = "serialize_wloutput")]
+        output: Option<WlOutput>,
+        workspaces: Vec<Workspace>,
+    }
+    for group in groups.iter() {
+        let workspaces = workspaces
             .iter()
-            .filter(|ws| ws.group.is_none())
+            .filter(|ws| ws.group.clone().is_some_and(|g| g == group.handle))
             .cloned()
             .collect::<Vec<_>>();
-        if !unassigned_workspaces.is_empty() {
-            state.serialize_element(&GroupSerialize {
-                output: None,
-                workspaces: unassigned_workspaces,
-            })?;
+        if !workspaces.is_empty() {
+            let group_s = GroupSerialize {
+                output: group.output.clone(),
+                workspaces,
+            };
+            state.serialize_element(&group_s)?;
         }
-        state.end()
     }
+
+    // unassigned workspaces
+    let unassigned_workspaces = workspaces
+        .iter()
+        .filter(|ws| ws.group.is_none())
+        .cloned()
+        .collect::<Vec<_>>();
+    if !unassigned_workspaces.is_empty() {
+        state.serialize_element(&GroupSerialize {
+            output: None,
+            workspaces: unassigned_workspaces,
+        })?;
+    }
+    state.end()
 }
 
 fn serialize_wloutput<S>(x: &Option<WlOutput>, s: S) -> Result<S::Ok, S::Error>
@@ -433,33 +644,18 @@ where
             let mut s = s.serialize_struct("Output", 5)?;
             s.serialize_field("protocolId", &output.id().protocol_id())?;
             s.serialize_field("name", &info.clone().and_then(|info| info.name))?;
-            s.serialize_field(
-                "location",
-                &info.clone().and_then(|info| Some(info.location)),
-            )?;
+            s.serialize_field("location", &info.clone().map(|info| info.location))?;
             s.serialize_field(
                 "description",
                 &info.clone().and_then(|info| info.description),
             )?;
-            s.serialize_field("globalId", &info.clone().and_then(|info| Some(info.id)))?;
+            s.serialize_field("globalId", &info.clone().map(|info| info.id))?;
             s.end()
         }
         None => s.serialize_none(),
     }
 }
 
-impl Serialize for TilingState {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: Serializer,
-    {
-        match self {
-            TilingState::FloatingOnly => serializer.serialize_str("FloatingOnly"),
-            TilingState::TilingEnabled => serializer.serialize_str("TilingEnabled"),
-        }
-    }
-}
-
 impl Display for WorkspaceStates {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         bitflags::parser::to_writer_strict(self, f)
@@ -491,8 +687,10 @@ impl Display for Workspace {
     }
 }
 
+// workspaces are numbered through all groups as printed, like `--index` counts them
 impl Display for WorkspaceState {
     fn fmt(&self, out: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut position = 0;
         for group in self.groups.iter() {
             writeln!(out, "{}", group)?;
             for workspace in self
@@ -500,7 +698,8 @@ impl Display for WorkspaceState {
                 .iter()
                 .filter(|ws| ws.group.as_ref().is_some_and(|g| g == &group.handle))
             {
-                writeln!(out, "    {}", workspace)?;
+                position += 1;
+                writeln!(out, "    {position}: {}", workspace)?;
             }
         }
 
@@ -512,7 +711,8 @@ impl Display for WorkspaceState {
         if !unassigned_ws.is_empty() {
             writeln!(out, "workspaces without assigned workspace group")?;
             for workspace in unassigned_ws {
-                writeln!(out, "    {}", workspace)?;
+                position += 1;
+                writeln!(out, "    {position}: {}", workspace)?;
             }
         }
         Ok(())
@@ -534,3 +734,262 @@ impl Display for WorkspaceGroup {
         )
     }
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::workspace_backend::{GroupBackend, ManagerBackend, WorkspaceBackend};
+    use std::os::unix::net::UnixStream;
+    use wayland_client::Connection;
+
+    // stands in for protocol objects, the number identifies the object
+    #[derive(Debug, PartialEq)]
+    struct Fake(u32);
+
+    impl ManagerBackend for Fake {
+        fn commit(&self) {}
+        fn stop(&self) {}
+    }
+
+    impl GroupBackend for Fake {
+        fn protocol_id(&self) -> u32 {
+            self.0
+        }
+        fn create_workspace(&self, _name: String) {}
+        fn destroy(&self) {}
+    }
+
+    impl WorkspaceBackend for Fake {
+        fn protocol_id(&self) -> u32 {
+            self.0
+        }
+        fn activate(&self) {}
+        fn deactivate(&self) {}
+        fn remove(&self) {}
+        fn destroy(&self) {}
+    }
+
+    pub(crate) fn state() -> WorkspaceState {
+        WorkspaceState {
+            groups: Vec::new(),
+            workspaces: Vec::new(),
+            manager: ManagerHandle::new(Fake(0)),
+            events: Vec::new(),
+            protocol: Protocol::ExtV1,
+            finished: false,
+            batches: None,
+            order: WorkspaceOrder::default(),
+        }
+    }
+
+    pub(crate) fn group(id: u32) -> GroupHandle {
+        GroupHandle::new(Fake(id))
+    }
+
+    pub(crate) fn workspace(id: u32) -> WorkspaceHandle {
+        WorkspaceHandle::new(Fake(id))
+    }
+
+    // inert proxy on a connection nobody serves, enough for output enter/leave events
+    pub(crate) fn output() -> (Connection, WlOutput) {
+        let (socket, _) = UnixStream::pair().unwrap();
+        let conn = Connection::from_socket(socket).unwrap();
+        let output = WlOutput::inert(conn.backend().downgrade());
+        (conn, output)
+    }
+
+    type Summary = Vec<(u32, Option<String>, Vec<u32>, WorkspaceStates, Option<u32>)>;
+
+    fn summary(state: &WorkspaceState) -> (Vec<(u32, bool)>, Summary) {
+        let mut groups = state
+            .groups
+            .iter()
+            .map(|g| (g.id(), g.output.is_some()))
+            .collect::<Vec<_>>();
+        groups.sort();
+        let mut workspaces = state
+            .workspaces
+            .iter()
+            .map(|ws| {
+                (
+                    ws.id(),
+                    ws.name.clone(),
+                    ws.coordinates.clone(),
+                    ws.state.clone(),
+                    ws.group.as_ref().map(|g| g.protocol_id()),
+                )
+            })
+            .collect::<Vec<_>>();
+        workspaces.sort_by_key(|ws| ws.0);
+        (groups, workspaces)
+    }
+
+    pub(crate) fn apply(state: &mut WorkspaceState, events: Vec<WorkspaceEvent>) {
+        state.events = events;
+        state.handle_events();
+    }
+
+    #[test]
+    fn events_are_applied_once() {
+        let mut state = state();
+        apply(
+            &mut state,
+            vec![
+                WorkspaceEvent::WorkspaceGroupCreated(group(1)),
+                WorkspaceEvent::WorkspaceCreated(Some(group(1)), workspace(2)),
+            ],
+        );
+        state.handle_events();
+        assert_eq!(state.groups.len(), 1);
+        assert_eq!(state.workspaces.len(), 1);
+    }
+
+    #[test]
+    fn unknown_handles_are_skipped() {
+        let (_conn, output) = output();
+        let mut state = state();
+        apply(
+            &mut state,
+            vec![
+                WorkspaceEvent::OutputEnter(group(1), output),
+                WorkspaceEvent::WorkspaceName(workspace(2), "web".to_string()),
+                WorkspaceEvent::WorkspaceEnter(workspace(2), group(1)),
+                WorkspaceEvent::WorkspaceCreated(Some(group(3)), workspace(4)),
+                WorkspaceEvent::WorkspaceRemoved(workspace(5)),
+                WorkspaceEvent::WorkspaceGroupRemoved(group(6)),
+            ],
+        );
+        assert!(state.groups.is_empty());
+        assert_eq!(summary(&state).1, [(4, None, vec![], WorkspaceStates::empty(), None)]);
+    }
+
+    #[test]
+    fn removed_group_unassigns_workspaces() {
+        let mut state = state();
+        apply(
+            &mut state,
+            vec![
+                WorkspaceEvent::WorkspaceGroupCreated(group(1)),
+                WorkspaceEvent::WorkspaceCreated(Some(group(1)), workspace(2)),
+            ],
+        );
+        apply(&mut state, vec![WorkspaceEvent::WorkspaceGroupRemoved(group(1))]);
+        assert!(state.groups.is_empty());
+        assert!(state.workspaces[0].group.is_none());
+    }
+
+    // a batch like a compositor would send it, every property is set at most once
+    fn random_batch(rng: &mut fastrand::Rng, output: &WlOutput) -> Vec<WorkspaceEvent> {
+        let mut events = Vec::new();
+        let groups = rng.u32(1..4);
+        for g in 1..=groups {
+            events.push(WorkspaceEvent::WorkspaceGroupCreated(group(g)));
+            if g == 1 {
+                events.push(WorkspaceEvent::OutputEnter(group(g), output.clone()));
+            }
+            events.push(WorkspaceEvent::WorkspaceGroupCapabilities(
+                group(g),
+                GroupCapabilities::CreateWorkspace,
+            ));
+        }
+        for w in 100..100 + rng.u32(0..8) {
+            let g = rng.u32(1..=groups);
+            if rng.bool() {
+                events.push(WorkspaceEvent::WorkspaceCreated(Some(group(g)), workspace(w)));
+            } else {
+                events.push(WorkspaceEvent::WorkspaceCreated(None, workspace(w)));
+                events.push(WorkspaceEvent::WorkspaceEnter(workspace(w), group(g)));
+            }
+            events.push(WorkspaceEvent::WorkspaceName(workspace(w), format!("ws-{w}")));
+            events.push(WorkspaceEvent::WorkspaceCoord(workspace(w), vec![rng.u32(0..4)]));
+            events.push(WorkspaceEvent::WorkspaceState(
+                workspace(w),
+                WorkspaceStates::from_bits_truncate(rng.u32(0..8)),
+            ));
+            if rng.u8(0..4) == 0 {
+                events.push(WorkspaceEvent::WorkspaceRemoved(workspace(w)));
+            }
+        }
+        if groups > 1 && rng.bool() {
+            events.push(WorkspaceEvent::WorkspaceGroupRemoved(group(groups)));
+        }
+        // references to objects the compositor never announced
+        for _ in 0..rng.usize(0..3) {
+            events.push(WorkspaceEvent::WorkspaceState(
+                workspace(rng.u32(200..210)),
+                WorkspaceStates::Active,
+            ));
+            events.push(WorkspaceEvent::WorkspaceEnter(workspace(100), group(rng.u32(50..60))));
+        }
+        events
+    }
+
+    #[test]
+    fn shuffled_batch_gives_same_state() {
+        let (_conn, output) = output();
+        for seed in 0..500 {
+            let mut rng = fastrand::Rng::with_seed(seed);
+            let events = random_batch(&mut rng, &output);
+            let mut ordered = state();
+            apply(&mut ordered, events.clone());
+            for _ in 0..5 {
+                let mut shuffled_events = events.clone();
+                rng.shuffle(&mut shuffled_events);
+                let mut shuffled = state();
+                apply(&mut shuffled, shuffled_events);
+                assert_eq!(summary(&ordered), summary(&shuffled), "seed {seed}");
+            }
+        }
+    }
+
+    #[test]
+    fn arbitrary_event_sequences_keep_state_consistent() {
+        let (_conn, output) = output();
+        for seed in 0..500 {
+            let mut rng = fastrand::Rng::with_seed(seed);
+            let mut state = state();
+            for _ in 0..rng.usize(1..6) {
+                let events = (0..rng.usize(0..30))
+                    .map(|_| {
+                        let g = group(rng.u32(1..4));
+                        let w = workspace(rng.u32(100..106));
+                        match rng.u8(0..10) {
+                            0 => WorkspaceEvent::WorkspaceGroupCreated(g),
+                            1 => WorkspaceEvent::WorkspaceGroupRemoved(g),
+                            2 => WorkspaceEvent::WorkspaceCreated(rng.bool().then_some(g), w),
+                            3 => WorkspaceEvent::WorkspaceRemoved(w),
+                            4 => WorkspaceEvent::WorkspaceEnter(w, g),
+                            5 => WorkspaceEvent::WorkspaceLeave(w, g),
+                            6 => WorkspaceEvent::OutputEnter(g, output.clone()),
+                            7 => WorkspaceEvent::OutputLeave(g, output.clone()),
+                            8 => WorkspaceEvent::WorkspaceName(w, "name".to_string()),
+                            _ => WorkspaceEvent::WorkspaceState(w, WorkspaceStates::Urgent),
+                        }
+                    })
+                    .collect();
+                apply(&mut state, events);
+
+                assert!(state.events.is_empty());
+                for (i, ws) in state.workspaces.iter().enumerate() {
+                    assert!(!state.workspaces[..i].iter().any(|other| other.handle == ws.handle));
+                    if let Some(g) = &ws.group {
+                        assert!(state.groups.iter().any(|group| &group.handle == g), "seed {seed}");
+                    }
+                }
+                for (i, g) in state.groups.iter().enumerate() {
+                    assert!(!state.groups[..i].iter().any(|other| other.handle == g.handle));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn decode_coordinates() {
+        assert_eq!(decode_u32_array(&[]), Vec::<u32>::new());
+        assert_eq!(decode_u32_array(&wire_array(&[0, 255, 256, 70000])), [0, 255, 256, 70000]);
+        // trailing bytes of an incomplete value are dropped
+        let mut array = wire_array(&[3, 1]);
+        array.push(7);
+        assert_eq!(decode_u32_array(&array), [3, 1]);
+    }
+}
@@ -0,0 +1,1437 @@
+mod common;
+
+use common::{Change, Layout, MockCompositor};
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::process::{Child, Command, Output, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+use wsctrl::workspace_state::Protocol;
+
+const PROTOCOLS: [Protocol; 3] = [Protocol::ExtV0, Protocol::ExtV1, Protocol::CosmicV1];
+
+fn protocol_arg(protocol: Protocol) -> &'static str {
+    match protocol {
+        Protocol::ExtV0 => "ext-v0",
+        Protocol::ExtV1 => "ext-v1",
+        Protocol::CosmicV1 => "cosmic-v1",
+    }
+}
+
+fn run(protocol: Protocol, layout: Layout, args: &[&str]) -> (Output, Layout) {
+    let compositor = MockCompositor::listen(protocol, layout);
+    let mut cli_args = vec!["--protocol", protocol_arg(protocol)];
+    cli_args.extend_from_slice(args);
+    let output = compositor.wsctrl(&cli_args);
+    (output, compositor.stop())
+}
+
+fn temp_file(name: &str, content: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("wsctrl-{}-{name}", std::process::id()));
+    std::fs::write(&path, content).unwrap();
+    path
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).to_string()
+}
+
+#[test]
+fn activate_by_name() {
+    for protocol in PROTOCOLS {
+        let (output, layout) = run(
+            protocol,
+            Layout::two_outputs(),
+            &["activate", "--name", "mail", "--output-name", "DP-4"],
+        );
+        assert!(output.status.success(), "{protocol:?} {output:?}");
+        assert!(layout.workspace("mail").active, "{protocol:?}");
+        assert!(!layout.workspace("web").active, "{protocol:?}");
+        assert!(layout.workspace("main").active, "{protocol:?}");
+    }
+}
+
+#[test]
+fn deactivate_by_name() {
+    for protocol in PROTOCOLS {
+        let (output, layout) =
+            run(protocol, Layout::two_outputs(), &["deactivate", "--name", "web"]);
+        assert!(output.status.success(), "{protocol:?} {output:?}");
+        assert!(!layout.workspace("web").active, "{protocol:?}");
+    }
+}
+
+#[test]
+fn remove_by_name() {
+    for protocol in PROTOCOLS {
+        let (output, layout) = run(
+            protocol,
+            Layout::two_outputs(),
+            &["remove", "--name", "terminal", "--output-name", "DP-4"],
+        );
+        assert!(output.status.success(), "{protocol:?} {output:?}");
+        assert!(!layout.has_workspace("terminal"), "{protocol:?}");
+        assert!(layout.has_workspace("mail"), "{protocol:?}");
+    }
+}
+
+#[test]
+fn create_workspace() {
+    for protocol in PROTOCOLS {
+        let (output, layout) = run(
+            protocol,
+            Layout::two_outputs(),
+            &["create-workspace", "--workspace-name", "scratch", "--output-name", "eDP-1"],
+        );
+        assert!(output.status.success(), "{protocol:?} {output:?}");
+        assert_eq!(layout.workspace("scratch").group, Some(0), "{protocol:?}");
+    }
+}
+
+#[test]
+fn create_workspace_print_id() {
+    // the timeout applies without --wait
+    let (output, layout) = run(
+        Protocol::ExtV1,
+        Layout::two_outputs(),
+        &["--wait-timeout", "5000", "create-workspace", "--workspace-name", "scratch", "--output-name", "eDP-1", "--print-id"],
+    );
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(stdout(&output), "mock-4\n");
+    assert_eq!(layout.workspace("scratch").id.as_deref(), Some("mock-4"));
+
+    let (output, layout) = run(
+        Protocol::CosmicV1,
+        Layout::two_outputs(),
+        &["create-workspace", "--workspace-name", "scratch", "--output-name", "eDP-1", "--print-id"],
+    );
+    assert_eq!(output.status.code(), Some(4));
+    assert!(!layout.has_workspace("scratch"));
+}
+
+#[test]
+fn select_by_id() {
+    let (output, layout) = run(Protocol::ExtV1, Layout::two_outputs(), &["activate", "--id", "mock-2"]);
+    assert!(output.status.success(), "{output:?}");
+    assert!(layout.workspace("mail").active);
+
+    let (output, _) = run(Protocol::ExtV1, Layout::two_outputs(), &["activate", "--id", "unknown"]);
+    assert_eq!(output.status.code(), Some(5));
+    let (output, _) = run(Protocol::ExtV0, Layout::two_outputs(), &["activate", "--id", "mock-2"]);
+    assert_eq!(output.status.code(), Some(4));
+}
+
+#[test]
+fn select_output_by_info_and_position() {
+    let cases: [(&[&str], Option<usize>); 9] = [
+        (&["--output-model", "DP-4"], Some(1)),
+        (&["--output-description", "Mock - eDP"], Some(0)),
+        (&["--output-at", "100,100"], Some(1)),
+        (&["--output-at", "2600,50"], Some(0)),
+        (&["--output-leftmost"], Some(1)),
+        (&["--output-rightmost"], Some(0)),
+        (&["--output-index", "1"], Some(1)),
+        (&["--output-index", "-1"], Some(0)),
+        (&["--output-at", "2000,0"], None),
+    ];
+    for protocol in PROTOCOLS {
+        for (selection, group) in cases {
+            let args = [&["create-workspace", "--workspace-name", "scratch"], selection].concat();
+            let (output, layout) = run(protocol, Layout::two_outputs(), &args);
+            match group {
+                Some(group) => {
+                    assert!(output.status.success(), "{protocol:?} {selection:?} {output:?}");
+                    assert_eq!(layout.workspace("scratch").group, Some(group), "{protocol:?} {selection:?}");
+                }
+                None => assert_eq!(output.status.code(), Some(5), "{protocol:?} {selection:?}"),
+            }
+        }
+        let (output, _) = run(
+            protocol,
+            Layout::two_outputs(),
+            &["activate", "--name", "mail", "--output-make", "Mock"],
+        );
+        assert_eq!(output.status.code(), Some(6), "{protocol:?}");
+    }
+}
+
+#[test]
+fn remove_all_matching() {
+    let mut layout = Layout::two_outputs();
+    layout.add_workspace(0, "tmp-1");
+    layout.add_workspace(1, "tmp-2");
+    for protocol in PROTOCOLS {
+        let (output, after) = run(protocol, layout.clone(), &["remove", "--name-match", "tmp-*"]);
+        assert_eq!(output.status.code(), Some(6), "{protocol:?}");
+        assert_eq!(after.commits, 0, "{protocol:?}");
+
+        let (output, after) =
+            run(protocol, layout.clone(), &["remove", "--name-match", "tmp-*", "--all"]);
+        assert!(output.status.success(), "{protocol:?} {output:?}");
+        assert!(!after.has_workspace("tmp-1") && !after.has_workspace("tmp-2"), "{protocol:?}");
+        assert!(after.has_workspace("main"), "{protocol:?}");
+        assert_eq!(after.commits, 1, "{protocol:?}");
+    }
+}
+
+#[test]
+fn select_by_regex_and_state() {
+    let mut layout = Layout::two_outputs();
+    layout.workspaces[3].urgent = true;
+    for protocol in PROTOCOLS {
+        let (output, after) = run(protocol, layout.clone(), &["activate", "--urgent"]);
+        assert!(output.status.success(), "{protocol:?} {output:?}");
+        assert!(after.workspace("terminal").active, "{protocol:?}");
+
+        let (output, after) = run(
+            protocol,
+            layout.clone(),
+            &["deactivate", "--name-regex", "^(web|main)$", "--all"],
+        );
+        assert!(output.status.success(), "{protocol:?} {output:?}");
+        assert!(!after.workspace("web").active && !after.workspace("main").active, "{protocol:?}");
+
+        let (output, _) = run(protocol, layout.clone(), &["remove", "--inactive", "--output-name", "DP-4"]);
+        assert_eq!(output.status.code(), Some(6), "{protocol:?}");
+        let (output, _) = run(protocol, layout.clone(), &["remove", "--hidden", "--all"]);
+        assert_eq!(output.status.code(), Some(5), "{protocol:?}");
+        let (output, _) = run(protocol, layout.clone(), &["remove", "--name-regex", "("]);
+        assert_eq!(output.status.code(), Some(2), "{protocol:?}");
+    }
+}
+
+#[test]
+fn assign_to_output() {
+    let args = ["assign", "--name", "mail", "--target-output-name", "eDP-1"];
+    let (output, layout) = run(Protocol::ExtV1, Layout::two_outputs(), &args);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(layout.workspace("mail").group, Some(0));
+
+    // assign was only added in ext_v1
+    for protocol in [Protocol::ExtV0, Protocol::CosmicV1] {
+        let (output, layout) = run(protocol, Layout::two_outputs(), &args);
+        assert!(!output.status.success(), "{protocol:?}");
+        assert_eq!(layout.workspace("mail").group, Some(1), "{protocol:?}");
+    }
+}
+
+#[test]
+fn assign_relative_to_output() {
+    // DP-4 is left of eDP-1
+    let cases: [(&[&str], Option<usize>); 5] = [
+        (&["--name", "mail", "--direction", "right"], Some(0)),
+        (&["--name", "main", "--direction", "left"], Some(1)),
+        (&["--name", "main", "--direction", "right"], None),
+        (&["--name", "mail", "--direction", "down"], None),
+        (&["--name", "main", "--next-output"], Some(1)),
+    ];
+    for (selection, group) in cases {
+        let args = [&["assign"], selection].concat();
+        let (output, layout) = run(Protocol::ExtV1, Layout::two_outputs(), &args);
+        let name = selection[1];
+        match group {
+            Some(group) => {
+                assert!(output.status.success(), "{selection:?} {output:?}");
+                assert_eq!(layout.workspace(name).group, Some(group), "{selection:?}");
+            }
+            None => assert_eq!(output.status.code(), Some(5), "{selection:?}"),
+        }
+    }
+
+    let args = ["assign", "--name", "mail", "--next-output", "--follow"];
+    let (output, layout) = run(Protocol::ExtV1, Layout::two_outputs(), &args);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(layout.workspace("mail").group, Some(0));
+    assert!(layout.workspace("mail").active);
+    assert!(!layout.workspace("main").active);
+}
+
+#[test]
+fn unknown_workspace() {
+    for protocol in PROTOCOLS {
+        let (output, _) = run(protocol, Layout::two_outputs(), &["activate", "--name", "missing"]);
+        assert_eq!(output.status.code(), Some(5), "{protocol:?}");
+    }
+}
+
+#[test]
+fn list() {
+    for protocol in PROTOCOLS {
+        let (output, _) = run(protocol, Layout::two_outputs(), &["list"]);
+        assert!(output.status.success(), "{protocol:?} {output:?}");
+        let text = stdout(&output);
+        assert!(text.contains("name: \"DP-4\""), "{protocol:?} {text}");
+        assert!(text.contains("name: \"mail\""), "{protocol:?} {text}");
+
+        let (output, _) = run(
+            protocol,
+            Layout::two_outputs(),
+            &["ls", "--json", "--output-name", "DP-4"],
+        );
+        assert!(output.status.success(), "{protocol:?} {output:?}");
+        let json: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+        let groups = json.as_array().unwrap();
+        assert_eq!(groups.len(), 1, "{protocol:?}");
+        assert_eq!(groups[0]["output"]["name"], "DP-4", "{protocol:?}");
+        assert_eq!(groups[0]["workspaces"].as_array().unwrap().len(), 3, "{protocol:?}");
+    }
+}
+
+fn grid_layout() -> Layout {
+    let mut layout = Layout::two_outputs();
+    for (name, coordinates) in [("web", [0, 0]), ("mail", [256, 0]), ("terminal", [256, 1])] {
+        let workspace = layout.workspaces.iter_mut().find(|ws| ws.name == name).unwrap();
+        workspace.coordinates = coordinates.to_vec();
+    }
+    layout
+}
+
+#[test]
+fn select_by_coordinates() {
+    for protocol in PROTOCOLS {
+        let (output, layout) = run(
+            protocol,
+            grid_layout(),
+            &["activate", "--coordinates", "256,1", "--output-name", "DP-4"],
+        );
+        assert!(output.status.success(), "{protocol:?} {output:?}");
+        assert!(layout.workspace("terminal").active, "{protocol:?}");
+        assert!(!layout.workspace("mail").active, "{protocol:?}");
+    }
+}
+
+#[test]
+fn list_coordinates() {
+    for protocol in PROTOCOLS {
+        let (output, _) = run(protocol, grid_layout(), &["ls", "--json", "--output-name", "DP-4"]);
+        assert!(output.status.success(), "{protocol:?} {output:?}");
+        let json: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+        let coordinates = json[0]["workspaces"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|ws| ws["coordinates"].clone())
+            .collect::<Vec<_>>();
+        // listed in coordinate order
+        assert_eq!(
+            coordinates,
+            [
+                serde_json::json!([0, 0]),
+                serde_json::json!([256, 0]),
+                serde_json::json!([256, 1])
+            ],
+            "{protocol:?}"
+        );
+
+        let (output, _) = run(protocol, grid_layout(), &["ls", "--output-name", "DP-4"]);
+        assert!(stdout(&output).contains("coordinates: [256, 1]"), "{protocol:?}");
+    }
+}
+
+#[test]
+fn index_follows_list_order() {
+    // coordinates reverse the creation order
+    let mut layout = Layout::two_outputs();
+    for (name, coordinate) in [("web", 2), ("mail", 1), ("terminal", 0)] {
+        let workspace = layout.workspaces.iter_mut().find(|ws| ws.name == name).unwrap();
+        workspace.coordinates = vec![coordinate];
+    }
+    let cases = [
+        ("coordinates", ["terminal", "mail", "web"]),
+        ("protocol-id", ["web", "mail", "terminal"]),
+        ("name", ["mail", "terminal", "web"]),
+        ("creation", ["web", "mail", "terminal"]),
+    ];
+    for protocol in PROTOCOLS {
+        for (order, names) in cases {
+            let (output, _) = run(
+                protocol,
+                layout.clone(),
+                &["--order", order, "ls", "--json", "--output-name", "DP-4"],
+            );
+            assert!(output.status.success(), "{protocol:?} {order} {output:?}");
+            let json: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+            let listed = json[0]["workspaces"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|ws| ws["name"].as_str().unwrap().to_string())
+                .collect::<Vec<_>>();
+            assert_eq!(listed, names, "{protocol:?} {order}");
+
+            // the text output numbers the workspaces like --index counts them
+            let (output, _) = run(protocol, layout.clone(), &["--order", order, "ls"]);
+            let text = stdout(&output);
+            for (position, name) in ["main"].iter().chain(&names).enumerate() {
+                let line = format!("    {}: name: \"{name}\"", position + 1);
+                assert!(text.contains(&line), "{protocol:?} {order} {text}");
+            }
+
+            for (index, name) in [("1", names[0]), ("-1", names[2])] {
+                let (output, layout) = run(
+                    protocol,
+                    layout.clone(),
+                    &["--order", order, "activate", "--index", index, "--output-name", "DP-4"],
+                );
+                assert!(output.status.success(), "{protocol:?} {order} {index} {output:?}");
+                assert!(layout.workspace(name).active, "{protocol:?} {order} {index}");
+            }
+        }
+
+        // global indices count through the outputs as listed
+        let (output, layout) = run(protocol, layout.clone(), &["deactivate", "--index", "1"]);
+        assert!(output.status.success(), "{protocol:?} {output:?}");
+        assert!(!layout.workspace("main").active, "{protocol:?}");
+
+        // indices used to start at 0, tell users of old key bindings
+        let (output, _) = run(protocol, layout.clone(), &["activate", "--index", "0"]);
+        assert_eq!(output.status.code(), Some(2), "{protocol:?}");
+        assert!(String::from_utf8_lossy(&output.stderr).contains("not 0 anymore"), "{output:?}");
+        let (output, _) = run(protocol, layout.clone(), &["activate", "--index", "-5"]);
+        assert_eq!(output.status.code(), Some(5), "{protocol:?}");
+    }
+}
+
+#[test]
+fn select_active() {
+    for protocol in PROTOCOLS {
+        let (output, layout) = run(
+            protocol,
+            Layout::two_outputs(),
+            &["deactivate", "--active", "--output-name", "DP-4"],
+        );
+        assert!(output.status.success(), "{protocol:?} {output:?}");
+        assert!(!layout.workspace("web").active, "{protocol:?}");
+        assert!(layout.workspace("main").active, "{protocol:?}");
+    }
+}
+
+#[test]
+fn list_states_and_capabilities() {
+    let mut layout = Layout::two_outputs();
+    let mail = layout.workspaces.iter_mut().find(|ws| ws.name == "mail").unwrap();
+    mail.urgent = true;
+    mail.hidden = true;
+    for protocol in PROTOCOLS {
+        let (output, _) = run(protocol, layout.clone(), &["ls", "--output-name", "DP-4"]);
+        assert!(output.status.success(), "{protocol:?} {output:?}");
+        let text = stdout(&output);
+        let line = |name: &str| {
+            text.lines()
+                .find(|l| l.contains(&format!("name: \"{name}\"")))
+                .unwrap()
+                .to_string()
+        };
+        assert!(line("web").contains("states: [Active]"), "{protocol:?} {text}");
+        assert!(line("mail").contains("states: [Hidden | Urgent]"), "{protocol:?} {text}");
+        match protocol {
+            Protocol::ExtV0 => assert!(line("web").contains("capabilities: []")),
+            Protocol::ExtV1 => assert!(
+                line("web").contains("capabilities: [Activate | Deactivate | Remove | Assign]"),
+                "{text}"
+            ),
+            Protocol::CosmicV1 => assert!(
+                line("web").contains(
+                    "capabilities: [Activate | Deactivate | Remove | Rename | SetTilingState]"
+                ),
+                "{text}"
+            ),
+        }
+    }
+}
+
+#[test]
+fn rename() {
+    for protocol in PROTOCOLS {
+        let (output, layout) = run(
+            protocol,
+            Layout::two_outputs(),
+            &["rename", "--name", "mail", "--new-name", "inbox"],
+        );
+        if protocol == Protocol::CosmicV1 {
+            assert!(output.status.success(), "{output:?}");
+            assert!(layout.has_workspace("inbox"));
+            assert!(!layout.has_workspace("mail"));
+        } else {
+            assert!(!output.status.success(), "{protocol:?}");
+            assert!(layout.has_workspace("mail"), "{protocol:?}");
+        }
+    }
+}
+
+#[test]
+fn set_tiling_state() {
+    let (output, layout) = run(
+        Protocol::CosmicV1,
+        Layout::two_outputs(),
+        &["set-tiling-state", "--name", "web", "tiling-enabled"],
+    );
+    assert!(output.status.success(), "{output:?}");
+    assert!(layout.workspace("web").tiling);
+    assert!(!layout.workspace("mail").tiling);
+}
+
+#[test]
+fn cosmic_version_1_has_no_rename() {
+    let compositor = MockCompositor::listen_version(Protocol::CosmicV1, 1, Layout::two_outputs());
+    let output = compositor.wsctrl(&[
+        "--protocol",
+        "cosmic-v1",
+        "rename",
+        "--name",
+        "mail",
+        "--new-name",
+        "inbox",
+    ]);
+    let list = compositor.wsctrl(&["--protocol", "cosmic-v1", "ls"]);
+    let layout = compositor.stop();
+    assert_eq!(output.status.code(), Some(7));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("rename capability"));
+    assert!(layout.has_workspace("mail"));
+    assert!(!stdout(&list).contains("tiling_state"));
+}
+
+#[test]
+fn finished_manager() {
+    for protocol in PROTOCOLS {
+        let compositor = MockCompositor::listen(protocol, Layout::two_outputs());
+        compositor.finish();
+        let output = compositor.wsctrl(&[
+            "--protocol",
+            protocol_arg(protocol),
+            "activate",
+            "--name",
+            "mail",
+        ]);
+        compositor.stop();
+        assert_eq!(output.status.code(), Some(9), "{protocol:?}");
+        assert!(
+            String::from_utf8_lossy(&output.stderr).contains("finished the workspace manager"),
+            "{protocol:?} {output:?}"
+        );
+    }
+}
+
+#[test]
+fn listen_exits_on_finished() {
+    for protocol in PROTOCOLS {
+        let compositor = MockCompositor::listen(protocol, Layout::two_outputs());
+        let mut listen = spawn_ready(
+            compositor.command(&["--protocol", protocol_arg(protocol), "listen"]),
+            INITIAL_STATE,
+        );
+        compositor.finish();
+        let start = Instant::now();
+        let status = loop {
+            if let Some(status) = listen.try_wait().unwrap() {
+                break status;
+            }
+            if start.elapsed() > Duration::from_secs(5) {
+                listen.kill().unwrap();
+                panic!("{protocol:?} listen did not exit after finished");
+            }
+            thread::sleep(Duration::from_millis(10));
+        };
+        compositor.stop();
+        assert!(status.success(), "{protocol:?}");
+    }
+}
+
+// logged once the initial state is received
+const INITIAL_STATE: &str = "initial state with";
+
+// spawns a long running command and waits until it logged a line containing `ready`
+fn spawn_ready(mut command: Command, ready: &'static str) -> Child {
+    let mut child = command
+        .env("RUST_LOG", "wsctrl=debug")
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let stderr = BufReader::new(child.stderr.take().unwrap());
+    let (logged, logged_rx) = mpsc::channel();
+    // keeps draining stderr, so the command never blocks on a full pipe
+    thread::spawn(move || {
+        for line in stderr.lines().map_while(Result::ok) {
+            if line.contains(ready) {
+                let _ = logged.send(());
+            }
+        }
+    });
+    if logged_rx.recv_timeout(Duration::from_secs(5)).is_err() {
+        child.kill().unwrap();
+        panic!("{command:?} did not log {ready:?}");
+    }
+    child
+}
+
+// runs a long running command while the compositor applies the changes, until it finishes the manager
+fn run_during(protocol: Protocol, args: &[&str], changes: Vec<Change>) -> Output {
+    let compositor = MockCompositor::listen(protocol, Layout::two_outputs());
+    let child = spawn_ready(
+        compositor.command(&[&["--protocol", protocol_arg(protocol)], args].concat()),
+        INITIAL_STATE,
+    );
+    for change in changes {
+        compositor.apply(change);
+    }
+    compositor.finish();
+    let output = child.wait_with_output().unwrap();
+    compositor.stop();
+    assert!(output.status.success(), "{protocol:?}");
+    output
+}
+
+fn watch(protocol: Protocol, args: &[&str], changes: Vec<Change>) -> Vec<serde_json::Value> {
+    let output = run_during(protocol, &[&["watch"], args].concat(), changes);
+    stdout(&output)
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+#[test]
+fn watch_changes() {
+    for protocol in PROTOCOLS {
+        let events = watch(
+            protocol,
+            &[],
+            vec![
+                Change::Activate(2),
+                Change::Urgent(3, true),
+                Change::Rename(0, "home".to_string()),
+            ],
+        );
+        // the compositor may send the changes in one batch or several
+        let mut summary = events
+            .iter()
+            .map(|event| {
+                format!(
+                    "{} {} {}",
+                    event["event"].as_str().unwrap(),
+                    event["workspace"]["name"].as_str().unwrap(),
+                    event["workspace"]["output"].as_str().unwrap()
+                )
+            })
+            .collect::<Vec<_>>();
+        summary.sort();
+        assert_eq!(
+            summary,
+            [
+                "activated mail DP-4",
+                "deactivated web DP-4",
+                "renamed home eDP-1",
+                "urgent terminal DP-4",
+            ],
+            "{protocol:?}"
+        );
+        let renamed = events.iter().find(|event| event["event"] == "renamed").unwrap();
+        assert_eq!(renamed["from"], "main", "{protocol:?}");
+    }
+}
+
+#[test]
+fn watch_full() {
+    for protocol in PROTOCOLS {
+        let states = watch(protocol, &["--full"], vec![Change::Rename(0, "home".to_string())]);
+        assert_eq!(states.len(), 2, "{protocol:?}");
+        let name = |state: &serde_json::Value| state[0]["workspaces"][0]["name"].clone();
+        assert_eq!(name(&states[0]), "main", "{protocol:?}");
+        assert_eq!(name(&states[1]), "home", "{protocol:?}");
+    }
+}
+
+#[test]
+fn hooks() {
+    for protocol in PROTOCOLS {
+        let log = std::env::temp_dir().join(format!("wsctrl-{}-hooks.log", std::process::id()));
+        let _ = std::fs::remove_file(&log);
+        let config = temp_file(
+            "hooks.toml",
+            &format!(
+                "[hooks]\non_activate = 'echo \"$WSCTRL_EVENT $WSCTRL_WORKSPACE_NAME $WSCTRL_WORKSPACE_OUTPUT\" >> {0}'\n\
+                 on_rename = 'echo \"renamed $WSCTRL_FROM\" >> {0}'\n",
+                log.display()
+            ),
+        );
+        run_during(
+            protocol,
+            &["hooks", "--config", config.to_str().unwrap()],
+            vec![Change::Activate(2), Change::Urgent(3, true)],
+        );
+        let mut lines = std::fs::read_to_string(&log)
+            .unwrap_or_default()
+            .lines()
+            .map(str::to_string)
+            .collect::<Vec<_>>();
+        lines.sort();
+        assert_eq!(lines, ["activated mail DP-4"], "{protocol:?}");
+    }
+}
+
+#[test]
+fn watch_runs_hooks() {
+    for protocol in PROTOCOLS {
+        let log = std::env::temp_dir().join(format!("wsctrl-{}-watch-hooks.log", std::process::id()));
+        let _ = std::fs::remove_file(&log);
+        let config = temp_file(
+            "watch-hooks.toml",
+            &format!("[hooks]\non_urgent = 'echo \"$WSCTRL_WORKSPACE_NAME\" >> {}'\n", log.display()),
+        );
+        let events = watch(
+            protocol,
+            &["--config", config.to_str().unwrap()],
+            vec![Change::Urgent(3, true)],
+        );
+        assert_eq!(events.len(), 1, "{protocol:?}");
+        assert_eq!(events[0]["event"], "urgent", "{protocol:?}");
+        assert_eq!(std::fs::read_to_string(&log).unwrap(), "terminal\n", "{protocol:?}");
+    }
+}
+
+#[test]
+fn hooks_invalid_config() {
+    let config = temp_file("invalid.toml", "[hooks]\non_activation = 'true'\n");
+    let (output, _) = run(
+        Protocol::ExtV1,
+        Layout::two_outputs(),
+        &["hooks", "--config", config.to_str().unwrap()],
+    );
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn wait_for_condition() {
+    for protocol in PROTOCOLS {
+        run_during(
+            protocol,
+            &["wait", "--name", "scratch", "--output-name", "DP-4", "--exists", "--timeout", "5000"],
+            vec![Change::Create(1, "scratch".to_string())],
+        );
+        run_during(protocol, &["wait", "--urgent"], vec![Change::Urgent(2, true)]);
+        run_during(
+            protocol,
+            &["wait", "--name", "mail", "--removed"],
+            vec![Change::Remove(2)],
+        );
+        run_during(
+            protocol,
+            &["wait", "--name-match", "scr*", "--exists", "--timeout", "5000"],
+            vec![Change::Create(1, "scratch".to_string())],
+        );
+    }
+}
+
+#[test]
+fn wait_timeout() {
+    for protocol in PROTOCOLS {
+        let (output, _) = run(
+            protocol,
+            Layout::two_outputs(),
+            &["wait", "--name", "web", "--output-name", "eDP-1", "--exists", "--timeout", "100"],
+        );
+        assert_eq!(output.status.code(), Some(11), "{protocol:?}");
+        let (output, _) = run(protocol, Layout::two_outputs(), &["wait", "--name", "web", "--active"]);
+        assert!(output.status.success(), "{protocol:?}");
+    }
+}
+
+#[test]
+fn ambiguous_name() {
+    let mut layout = Layout::two_outputs();
+    layout.add_workspace(0, "web");
+    for protocol in PROTOCOLS {
+        let (output, _) = run(protocol, layout.clone(), &["activate", "--name", "web"]);
+        assert_eq!(output.status.code(), Some(6), "{protocol:?} {output:?}");
+
+        let (output, layout) = run(
+            protocol,
+            layout.clone(),
+            &["deactivate", "--name", "web", "--output-name", "DP-4"],
+        );
+        assert!(output.status.success(), "{protocol:?} {output:?}");
+        assert!(!layout.workspace("web").active, "{protocol:?}");
+    }
+}
+
+#[test]
+fn protocol_unsupported() {
+    let compositor = MockCompositor::listen(Protocol::ExtV0, Layout::two_outputs());
+    let output = compositor.wsctrl(&["--protocol", "ext-v1", "list"]);
+    compositor.stop();
+    assert_eq!(output.status.code(), Some(4), "{output:?}");
+}
+
+#[test]
+fn no_compositor() {
+    let output = Command::new(env!("CARGO_BIN_EXE_wsctrl"))
+        .args(["list"])
+        .env("WAYLAND_DISPLAY", std::env::temp_dir().join("wsctrl-test-no-compositor"))
+        .env_remove("WAYLAND_SOCKET")
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(3), "{output:?}");
+}
+
+#[test]
+fn json_error() {
+    let (output, _) = run(
+        Protocol::ExtV1,
+        Layout::two_outputs(),
+        &["--json", "activate", "--name", "missing"],
+    );
+    assert_eq!(output.status.code(), Some(5));
+    let error: serde_json::Value = serde_json::from_slice(&output.stderr).unwrap();
+    assert_eq!(error["exitCode"], 5);
+    assert_eq!(error["error"]["kind"], "not_found");
+    assert_eq!(error["error"]["message"], "Unable to find workspace with name missing");
+}
+
+#[test]
+fn wait_for_applied_change() {
+    for protocol in PROTOCOLS {
+        for args in [
+            &["activate", "--name", "mail", "--output-name", "DP-4"][..],
+            &["deactivate", "--name", "web"],
+            &["remove", "--name", "terminal"],
+            &["create-workspace", "--workspace-name", "web", "--output-name", "eDP-1"],
+        ] {
+            let mut cli_args = vec!["--wait"];
+            cli_args.extend_from_slice(args);
+            let (output, _) = run(protocol, Layout::two_outputs(), &cli_args);
+            assert!(output.status.success(), "{protocol:?} {args:?} {output:?}");
+        }
+    }
+}
+
+#[test]
+fn wait_for_ignored_change() {
+    for protocol in PROTOCOLS {
+        let compositor = MockCompositor::listen(protocol, Layout::two_outputs());
+        compositor.ignore_requests();
+        let start = Instant::now();
+        let output = compositor.wsctrl(&[
+            "--protocol",
+            protocol_arg(protocol),
+            "--wait",
+            "--wait-timeout",
+            "200",
+            "activate",
+            "--name",
+            "mail",
+        ]);
+        compositor.stop();
+        assert!(start.elapsed() >= Duration::from_millis(200), "{protocol:?}");
+        assert_eq!(output.status.code(), Some(8), "{protocol:?} {output:?}");
+        assert!(
+            String::from_utf8_lossy(&output.stderr).contains("workspace \"mail\" is not active"),
+            "{protocol:?} {output:?}"
+        );
+
+        // without --wait the ignored change goes unnoticed
+        let compositor = MockCompositor::listen(protocol, Layout::two_outputs());
+        compositor.ignore_requests();
+        let output =
+            compositor.wsctrl(&["--protocol", protocol_arg(protocol), "remove", "--name", "mail"]);
+        assert!(compositor.stop().has_workspace("mail"));
+        assert!(output.status.success(), "{protocol:?}");
+    }
+}
+
+fn active_on(layout: &Layout, group: usize) -> Vec<&str> {
+    layout
+        .workspaces
+        .iter()
+        .filter(|ws| ws.group == Some(group) && ws.active)
+        .map(|ws| ws.name.as_str())
+        .collect()
+}
+
+#[test]
+fn next_and_prev() {
+    for protocol in PROTOCOLS {
+        for (args, active) in [
+            (&["next", "--output-name", "DP-4"][..], "mail"),
+            (&["prev", "--output-name", "DP-4"], "terminal"),
+            (&["prev", "--output-name", "DP-4", "--no-wrap", "--wrap"], "terminal"),
+        ] {
+            let (output, layout) = run(protocol, Layout::two_outputs(), args);
+            assert!(output.status.success(), "{protocol:?} {args:?} {output:?}");
+            assert_eq!(active_on(&layout, 1), [active], "{protocol:?} {args:?}");
+            assert_eq!(active_on(&layout, 0), ["main"], "{protocol:?} {args:?}");
+        }
+    }
+}
+
+#[test]
+fn next_without_wrap() {
+    for protocol in PROTOCOLS {
+        let (output, layout) =
+            run(protocol, Layout::two_outputs(), &["prev", "--output-name", "DP-4", "--no-wrap"]);
+        assert_eq!(output.status.code(), Some(5), "{protocol:?} {output:?}");
+        assert_eq!(active_on(&layout, 1), ["web"], "{protocol:?}");
+
+        // the only workspace of eDP-1 has no neighbours
+        let (output, _) = run(protocol, Layout::two_outputs(), &["next", "--output-name", "eDP-1"]);
+        assert_eq!(output.status.code(), Some(5), "{protocol:?} {output:?}");
+    }
+}
+
+#[test]
+fn next_skip_hidden() {
+    let mut layout = Layout::two_outputs();
+    layout.workspaces.iter_mut().find(|ws| ws.name == "mail").unwrap().hidden = true;
+    for protocol in PROTOCOLS {
+        let (output, result) = run(
+            protocol,
+            layout.clone(),
+            &["next", "--output-name", "DP-4", "--skip-hidden"],
+        );
+        assert!(output.status.success(), "{protocol:?} {output:?}");
+        assert_eq!(active_on(&result, 1), ["terminal"], "{protocol:?}");
+
+        let (output, result) = run(protocol, layout.clone(), &["next", "--output-name", "DP-4"]);
+        assert!(output.status.success(), "{protocol:?} {output:?}");
+        assert_eq!(active_on(&result, 1), ["mail"], "{protocol:?}");
+    }
+}
+
+#[test]
+fn next_requires_output_with_several_outputs() {
+    let (output, _) = run(Protocol::ExtV1, Layout::two_outputs(), &["next"]);
+    assert_eq!(output.status.code(), Some(2), "{output:?}");
+
+    let mut layout = Layout::default();
+    layout.add_output("DP-1", (0, 0));
+    layout.add_workspace(0, "one");
+    layout.add_workspace(0, "two");
+    layout.workspaces[0].active = true;
+    let (output, layout) = run(Protocol::ExtV1, layout, &["next"]);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(active_on(&layout, 0), ["two"]);
+}
+
+// a b c
+// d _ f
+fn gap_grid(active: &str) -> Layout {
+    let mut layout = Layout::default();
+    layout.add_output("DP-1", (0, 0));
+    for (name, coordinates) in [
+        ("a", [0, 0]),
+        ("b", [1, 0]),
+        ("c", [2, 0]),
+        ("d", [0, 1]),
+        ("f", [2, 1]),
+    ] {
+        let workspace = layout.add_workspace(0, name);
+        layout.workspaces[workspace].coordinates = coordinates.to_vec();
+        layout.workspaces[workspace].active = name == active;
+    }
+    layout
+}
+
+#[test]
+fn move_focus() {
+    for protocol in PROTOCOLS {
+        for (from, direction, to) in [
+            ("b", "right", "c"),
+            ("b", "left", "a"),
+            ("c", "down", "f"),
+            ("f", "left", "d"),
+            ("d", "up", "a"),
+        ] {
+            let (output, layout) = run(protocol, gap_grid(from), &["move-focus", direction]);
+            assert!(output.status.success(), "{protocol:?} {from} {direction} {output:?}");
+            assert_eq!(active_on(&layout, 0), [to], "{protocol:?} {from} {direction}");
+        }
+    }
+}
+
+#[test]
+fn move_focus_at_edge() {
+    for protocol in PROTOCOLS {
+        let (output, layout) = run(protocol, gap_grid("b"), &["move-focus", "up"]);
+        assert_eq!(output.status.code(), Some(5), "{protocol:?} {output:?}");
+        assert_eq!(active_on(&layout, 0), ["b"], "{protocol:?}");
+
+        let (output, layout) = run(
+            protocol,
+            gap_grid("b"),
+            &["move-focus", "up", "--create", "new"],
+        );
+        if protocol == Protocol::ExtV0 {
+            // ext_v0 groups advertise no capabilities
+            assert_eq!(output.status.code(), Some(7), "{output:?}");
+            assert!(!layout.has_workspace("new"));
+        } else {
+            assert!(output.status.success(), "{protocol:?} {output:?}");
+            assert_eq!(active_on(&layout, 0), ["new"], "{protocol:?}");
+        }
+    }
+}
+
+#[test]
+fn move_focus_needs_axis() {
+    let (output, _) = run(
+        Protocol::ExtV1,
+        Layout::two_outputs(),
+        &["move-focus", "down", "--output-name", "DP-4"],
+    );
+    assert_eq!(output.status.code(), Some(2), "{output:?}");
+
+    let (output, layout) = run(
+        Protocol::ExtV1,
+        Layout::two_outputs(),
+        &["move-focus", "right", "--output-name", "DP-4"],
+    );
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(active_on(&layout, 1), ["mail"]);
+}
+
+fn run_batch(protocol: Protocol, layout: Layout, batch: &str) -> (Output, Layout) {
+    let compositor = MockCompositor::listen(protocol, layout);
+    let mut child = compositor
+        .command(&["--protocol", protocol_arg(protocol), "batch"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(batch.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    (output, compositor.stop())
+}
+
+#[test]
+fn batch_from_stdin() {
+    for protocol in PROTOCOLS {
+        let (output, layout) = run_batch(
+            protocol,
+            Layout::two_outputs(),
+            "# switch both outputs\n\
+             deactivate --name main\n\
+             \n\
+             activate --name mail --output-name 'DP-4'\n",
+        );
+        assert!(output.status.success(), "{protocol:?} {output:?}");
+        assert_eq!(layout.commits, 1, "{protocol:?}");
+        assert!(!layout.workspace("main").active, "{protocol:?}");
+        assert_eq!(active_on(&layout, 1), ["mail"], "{protocol:?}");
+    }
+}
+
+#[test]
+fn batch_from_file() {
+    let path = temp_file(
+        "batch",
+        "deactivate --name main\n\
+         activate --name mail -o DP-4\n\
+         assign --name terminal -o DP-4 --target-output-name eDP-1\n",
+    );
+    let (output, layout) = run(
+        Protocol::ExtV1,
+        Layout::two_outputs(),
+        &["--wait", "batch", path.to_str().unwrap()],
+    );
+    std::fs::remove_file(&path).unwrap();
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(layout.commits, 1);
+    assert!(!layout.workspace("main").active);
+    assert!(layout.workspace("mail").active);
+    assert_eq!(layout.workspace("terminal").group, Some(0));
+}
+
+#[test]
+fn batch_is_all_or_nothing() {
+    for (batch, code) in [
+        ("activate --name mail -o DP-4\nactivate --name nope\n", 5),
+        ("activate --name mail -o DP-4\nactivate --bogus\n", 2),
+        ("activate --name mail -o DP-4\nlist\n", 2),
+        ("activate --name 'mail\n", 2),
+    ] {
+        let (output, layout) = run_batch(Protocol::ExtV1, Layout::two_outputs(), batch);
+        assert_eq!(output.status.code(), Some(code), "{batch:?} {output:?}");
+        assert_eq!(layout.commits, 0, "{batch:?}");
+        assert_eq!(active_on(&layout, 1), ["web"], "{batch:?}");
+    }
+}
+
+const LAYOUT: &str = r#"
+[[output]]
+name = "eDP-1"
+workspaces = ["main", "terminal", "scratch"]
+active = "scratch"
+
+[[output]]
+name = "DP-4"
+workspaces = ["web", "mail"]
+active = "mail"
+"#;
+
+#[test]
+fn apply_layout() {
+    let path = temp_file("layout.toml", LAYOUT);
+    let (output, layout) = run(
+        Protocol::ExtV1,
+        Layout::two_outputs(),
+        &["apply", path.to_str().unwrap()],
+    );
+    assert!(output.status.success(), "{output:?}");
+    // activating the created workspace needs a second commit
+    assert_eq!(layout.commits, 2);
+    assert_eq!(layout.workspace("terminal").group, Some(0));
+    assert_eq!(layout.workspace("scratch").group, Some(0));
+    assert_eq!(active_on(&layout, 0), ["scratch"]);
+    assert_eq!(active_on(&layout, 1), ["mail"]);
+
+    let layout = Layout { commits: 0, ..layout };
+    let (output, layout) = run(Protocol::ExtV1, layout, &["apply", path.to_str().unwrap()]);
+    std::fs::remove_file(&path).unwrap();
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(layout.commits, 0, "applied layout has nothing to do");
+}
+
+#[test]
+fn apply_dry_run() {
+    let path = temp_file("dry-run.toml", LAYOUT);
+    let (output, layout) = run(
+        Protocol::ExtV1,
+        Layout::two_outputs(),
+        &["apply", "--dry-run", "--remove-extra", path.to_str().unwrap()],
+    );
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(
+        stdout(&output),
+        "assign \"terminal\" from DP-4 to eDP-1\n\
+         create \"scratch\" on eDP-1\n\
+         activate \"scratch\" on eDP-1\n\
+         activate \"mail\" on DP-4\n"
+    );
+    assert_eq!(layout.commits, 0);
+
+    let (output, _) = run(
+        Protocol::ExtV1,
+        Layout::two_outputs(),
+        &["--json", "apply", "--dry-run", path.to_str().unwrap()],
+    );
+    std::fs::remove_file(&path).unwrap();
+    let plan: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(
+        plan[0],
+        serde_json::json!({"action": "assign", "workspace": "terminal", "from": "DP-4", "output": "eDP-1"})
+    );
+    assert_eq!(plan.as_array().unwrap().len(), 4);
+}
+
+#[test]
+fn apply_without_assign() {
+    let path = temp_file("no-assign.toml", LAYOUT);
+    for protocol in [Protocol::ExtV0, Protocol::CosmicV1] {
+        let (output, _) = run(
+            protocol,
+            Layout::two_outputs(),
+            &["apply", "--dry-run", path.to_str().unwrap()],
+        );
+        assert!(output.status.success(), "{protocol:?} {output:?}");
+        assert_eq!(
+            stdout(&output),
+            "create \"terminal\" on eDP-1\n\
+             create \"scratch\" on eDP-1\n\
+             activate \"scratch\" on eDP-1\n\
+             activate \"mail\" on DP-4\n",
+            "{protocol:?}"
+        );
+
+        let (output, layout) = run(protocol, Layout::two_outputs(), &["apply", path.to_str().unwrap()]);
+        assert!(output.status.success(), "{protocol:?} {output:?}");
+        assert_eq!(layout.workspace("terminal").group, Some(1), "{protocol:?}");
+        assert_eq!(active_on(&layout, 0), ["scratch"], "{protocol:?}");
+    }
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn apply_remove_extra() {
+    let path = temp_file(
+        "remove-extra.json",
+        r#"{"output": [{"name": "DP-4", "workspaces": ["web", "new"]}]}"#,
+    );
+    for protocol in PROTOCOLS {
+        let (output, layout) = run(
+            protocol,
+            Layout::two_outputs(),
+            &["--wait", "apply", "--remove-extra", path.to_str().unwrap()],
+        );
+        assert!(output.status.success(), "{protocol:?} {output:?}");
+        assert_eq!(layout.commits, 1, "{protocol:?}");
+        assert!(!layout.has_workspace("mail"), "{protocol:?}");
+        assert!(!layout.has_workspace("terminal"), "{protocol:?}");
+        assert_eq!(layout.workspace("new").group, Some(1), "{protocol:?}");
+        assert!(layout.has_workspace("main"), "{protocol:?}");
+    }
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn apply_invalid_layout() {
+    for (name, content, code) in [
+        ("inactive.toml", "[[output]]\nname = \"DP-4\"\nactive = \"web\"\n", 2),
+        ("unknown.toml", "[[output]]\nname = \"HDMI-1\"\n", 5),
+        ("syntax.json", "{\"output\": ", 2),
+    ] {
+        let path = temp_file(name, content);
+        let (output, layout) = run(
+            Protocol::ExtV1,
+            Layout::two_outputs(),
+            &["apply", path.to_str().unwrap()],
+        );
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(output.status.code(), Some(code), "{name} {output:?}");
+        assert_eq!(layout.commits, 0, "{name}");
+    }
+}
+
+#[test]
+fn save_and_restore() {
+    let path = std::env::temp_dir().join(format!("wsctrl-{}-snapshot.json", std::process::id()));
+    let path = path.to_str().unwrap();
+    for protocol in [Protocol::ExtV1, Protocol::CosmicV1] {
+        let (output, _) = run(protocol, Layout::two_outputs(), &["save", path]);
+        assert!(output.status.success(), "{protocol:?} {output:?}");
+
+        // after a compositor restart with fewer workspaces and different ones active
+        let mut layout = Layout::two_outputs();
+        let mail = layout.workspaces.iter().position(|ws| ws.name == "mail").unwrap();
+        layout.workspaces[mail].removed = true;
+        layout.workspaces.iter_mut().for_each(|ws| ws.active = ws.name == "terminal");
+        let (output, layout) = run(protocol, layout, &["--wait", "restore", path]);
+        assert!(output.status.success(), "{protocol:?} {output:?}");
+        assert_eq!(layout.workspace("mail").group, Some(1), "{protocol:?}");
+        assert_eq!(active_on(&layout, 0), ["main"], "{protocol:?}");
+        assert_eq!(active_on(&layout, 1), ["web"], "{protocol:?}");
+    }
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn save_in_order_with_unassigned() {
+    let path = std::env::temp_dir().join(format!("wsctrl-{}-order.json", std::process::id()));
+    let path = path.to_str().unwrap();
+    let mut layout = Layout::two_outputs();
+    layout.workspaces.iter_mut().find(|ws| ws.name == "main").unwrap().group = None;
+    let (output, _) = run(Protocol::ExtV1, layout, &["--order", "name", "save", path]);
+    assert!(output.status.success(), "{output:?}");
+    let snapshot: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+    std::fs::remove_file(path).unwrap();
+    let names = |workspaces: &serde_json::Value| {
+        workspaces.as_array().unwrap().iter().map(|ws| ws["name"].clone()).collect::<Vec<_>>()
+    };
+    let dp = snapshot["outputs"].as_array().unwrap().iter().find(|o| o["name"] == "DP-4").unwrap();
+    assert_eq!(names(&dp["workspaces"]), ["mail", "terminal", "web"]);
+    assert_eq!(names(&snapshot["unassigned"]), ["main"]);
+
+    let (output, _) = run(Protocol::ExtV1, Layout::two_outputs(), &["save", "/nonexistent/snapshot.json"]);
+    assert_eq!(output.status.code(), Some(10), "{output:?}");
+}
+
+#[test]
+fn restore_matches_id_and_description() {
+    let path = std::env::temp_dir().join(format!("wsctrl-{}-matching.json", std::process::id()));
+    let path = path.to_str().unwrap();
+    let (output, _) = run(Protocol::ExtV1, Layout::two_outputs(), &["save", path]);
+    assert!(output.status.success(), "{output:?}");
+
+    // renamed workspace keeps its ext-v1 id, the output got another connector name
+    let mut layout = Layout::two_outputs();
+    layout.workspaces.iter_mut().find(|ws| ws.name == "web").unwrap().name = "browser".to_string();
+    layout.outputs[1].name = "DP-5".to_string();
+    let terminal = layout.workspaces.iter().position(|ws| ws.name == "terminal").unwrap();
+    layout.workspaces[terminal].group = Some(0);
+    let (output, layout) = run(Protocol::ExtV1, layout, &["restore", "--dry-run", path]);
+    std::fs::remove_file(path).unwrap();
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(stdout(&output), "assign \"terminal\" from eDP-1 to DP-5\n");
+    assert_eq!(layout.commits, 0);
+}
+
+#[test]
+fn restore_without_assign() {
+    let path = std::env::temp_dir().join(format!("wsctrl-{}-no-assign.json", std::process::id()));
+    let path = path.to_str().unwrap();
+    let (output, _) = run(Protocol::CosmicV1, Layout::two_outputs(), &["save", path]);
+    assert!(output.status.success(), "{output:?}");
+
+    // after a docking change terminal ended up on the other output, cosmic-v1 can not move it back
+    let mut layout = Layout::two_outputs();
+    let terminal = layout.workspaces.iter().position(|ws| ws.name == "terminal").unwrap();
+    layout.workspaces[terminal].group = Some(0);
+    let (output, _) = run(Protocol::CosmicV1, layout.clone(), &["restore", "--dry-run", path]);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(stdout(&output), "create \"terminal\" on DP-4\n");
+
+    let (output, layout) = run(Protocol::CosmicV1, layout, &["--wait", "restore", path]);
+    std::fs::remove_file(path).unwrap();
+    assert!(output.status.success(), "{output:?}");
+    let groups = layout
+        .workspaces
+        .iter()
+        .filter(|ws| !ws.removed && ws.name == "terminal")
+        .map(|ws| ws.group)
+        .collect::<Vec<_>>();
+    assert_eq!(groups, [Some(0), Some(1)]);
+}
+
+fn runtime_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("wsctrl-{}-{name}-runtime", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir(&dir).unwrap();
+    dir
+}
+
+fn daemon_socket(dir: &std::path::Path, compositor: &MockCompositor) -> PathBuf {
+    dir.join(format!("wsctrl-{}.sock", compositor.display().replace('/', "_")))
+}
+
+// starts a daemon for the compositor and waits until it serves on its socket
+fn spawn_daemon(compositor: &MockCompositor, dir: &std::path::Path, protocol: Protocol) -> (Child, PathBuf) {
+    let mut command = compositor.command(&["--protocol", protocol_arg(protocol), "daemon"]);
+    command.env("XDG_RUNTIME_DIR", dir);
+    let daemon = spawn_ready(command, "serving workspace requests on");
+    (daemon, daemon_socket(dir, compositor))
+}
+
+// finishes the workspace manager and waits until the daemon exited
+fn stop_daemon(compositor: &MockCompositor, mut daemon: Child, socket: &std::path::Path) {
+    compositor.finish();
+    let (exited, exited_rx) = mpsc::channel();
+    thread::spawn(move || {
+        let _ = exited.send(daemon.wait().unwrap());
+    });
+    let status = exited_rx
+        .recv_timeout(Duration::from_secs(5))
+        .expect("daemon did not exit");
+    assert!(status.success());
+    assert!(!socket.exists());
+}
+
+#[test]
+fn daemon_serves_commands() {
+    let dir = runtime_dir("daemon");
+    let compositor = MockCompositor::listen(Protocol::ExtV1, Layout::two_outputs());
+    let wsctrl = |args: &[&str]| {
+        let mut command = compositor.command(args);
+        command.env("XDG_RUNTIME_DIR", &dir);
+        command
+    };
+    let (daemon, socket) = spawn_daemon(&compositor, &dir, Protocol::ExtV1);
+
+    // the mock compositor accepts a single client, the daemon, so these only succeed through it
+    let output = wsctrl(&["--wait", "activate", "--name", "mail", "-o", "DP-4"]).output().unwrap();
+    assert!(output.status.success(), "{output:?}");
+    let output = wsctrl(&["list", "-o", "DP-4"]).output().unwrap();
+    assert!(output.status.success(), "{output:?}");
+    let active = stdout(&output)
+        .lines()
+        .filter(|line| line.contains("states: [Active]"))
+        .map(str::trim)
+        .map(|line| line.split('"').nth(1).unwrap().to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(active, ["mail"]);
+    let output = wsctrl(&["activate", "--name", "nope"]).output().unwrap();
+    assert_eq!(output.status.code(), Some(5), "{output:?}");
+    let output = wsctrl(&["--no-daemon", "list"]).output().unwrap();
+    assert_eq!(output.status.code(), Some(3), "{output:?}");
+
+    stop_daemon(&compositor, daemon, &socket);
+    let layout = compositor.stop();
+    assert_eq!(active_on(&layout, 1), ["mail"]);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn daemon_failed_command_sends_nothing() {
+    let dir = runtime_dir("daemon-failed");
+    let mut layout = Layout::two_outputs();
+    layout.workspaces.iter_mut().find(|ws| ws.name == "terminal").unwrap().fixed_tiling = true;
+    let compositor = MockCompositor::listen(Protocol::CosmicV1, layout);
+    let wsctrl = |args: &[&str]| {
+        let mut command = compositor.command(args);
+        command.env("XDG_RUNTIME_DIR", &dir);
+        command.output().unwrap()
+    };
+    let (daemon, socket) = spawn_daemon(&compositor, &dir, Protocol::CosmicV1);
+
+    let output = wsctrl(&["set-tiling-state", "--name-match", "*", "--output-name", "DP-4", "--all", "tiling-enabled"]);
+    assert_eq!(output.status.code(), Some(7), "{output:?}");
+    // commits the requests of this command only
+    let output = wsctrl(&["--wait", "activate", "--name", "mail"]);
+    assert!(output.status.success(), "{output:?}");
+
+    stop_daemon(&compositor, daemon, &socket);
+    let layout = compositor.stop();
+    assert!(layout.workspaces.iter().all(|ws| !ws.tiling));
+    assert_eq!(active_on(&layout, 1), ["mail"]);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn daemon_failed_assign_sends_nothing() {
+    let dir = runtime_dir("daemon-assign");
+    let mut layout = Layout::two_outputs();
+    layout.workspaces.iter_mut().find(|ws| ws.name == "terminal").unwrap().fixed_output = true;
+    let compositor = MockCompositor::listen(Protocol::ExtV1, layout);
+    let wsctrl = |args: &[&str]| {
+        let mut command = compositor.command(args);
+        command.env("XDG_RUNTIME_DIR", &dir);
+        command.output().unwrap()
+    };
+    let (daemon, socket) = spawn_daemon(&compositor, &dir, Protocol::ExtV1);
+
+    // terminal comes last, the other workspaces of DP-4 could be assigned before it
+    let output = wsctrl(&["assign", "--name-match", "*", "--output-name", "DP-4", "--all", "--target-output-name", "eDP-1"]);
+    assert_eq!(output.status.code(), Some(7), "{output:?}");
+    let output = wsctrl(&["--wait", "activate", "--name", "mail"]);
+    assert!(output.status.success(), "{output:?}");
+
+    stop_daemon(&compositor, daemon, &socket);
+    let layout = compositor.stop();
+    assert!(["web", "mail", "terminal"].iter().all(|name| layout.workspace(name).group == Some(1)));
+    assert_eq!(active_on(&layout, 1), ["mail"]);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn stale_daemon_socket() {
+    let dir = runtime_dir("stale");
+    let compositor = MockCompositor::listen(Protocol::ExtV1, Layout::two_outputs());
+    // socket of a daemon that was killed
+    drop(std::os::unix::net::UnixListener::bind(daemon_socket(&dir, &compositor)).unwrap());
+    let output = compositor
+        .command(&["activate", "--name", "mail", "-o", "DP-4"])
+        .env("XDG_RUNTIME_DIR", &dir)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(active_on(&compositor.stop(), 1), ["mail"]);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn daemon_closes_connection() {
+    let dir = runtime_dir("closing");
+    let compositor = MockCompositor::listen(Protocol::ExtV1, Layout::two_outputs());
+    // a daemon that dies after accepting the request
+    let listener = std::os::unix::net::UnixListener::bind(daemon_socket(&dir, &compositor)).unwrap();
+    let closer = thread::spawn(move || drop(listener.accept().unwrap()));
+    let output = compositor
+        .command(&["activate", "--name", "mail", "-o", "DP-4"])
+        .env("XDG_RUNTIME_DIR", &dir)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    closer.join().unwrap();
+    assert_eq!(active_on(&compositor.stop(), 1), ["mail"]);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
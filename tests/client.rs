@@ -0,0 +1,122 @@
+mod common;
+
+use common::{Layout, MockCompositor};
+use wsctrl::cli::{OutputSelector, WorkspaceMatch, WorkspaceSelector};
+use wsctrl::workspace_state::Protocol;
+use wsctrl::{Error, WorkspaceClient};
+
+const PROTOCOLS: [Protocol; 3] = [Protocol::ExtV0, Protocol::ExtV1, Protocol::CosmicV1];
+
+fn output(name: &str) -> OutputSelector {
+    OutputSelector {
+        output_name: Some(name.to_string()),
+        ..Default::default()
+    }
+}
+
+fn workspace(name: &str) -> WorkspaceSelector {
+    WorkspaceSelector {
+        matching: WorkspaceMatch {
+            name: Some(name.to_string()),
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+#[test]
+fn snapshot() {
+    for protocol in PROTOCOLS {
+        let (compositor, conn) = MockCompositor::spawn(protocol, Layout::two_outputs());
+        let client = WorkspaceClient::from_connection(&conn, Some(protocol)).unwrap();
+
+        let state = client.state();
+        assert_eq!(state.protocol, protocol);
+        let mut outputs = state
+            .groups
+            .iter()
+            .filter_map(|g| g.get_output_name())
+            .collect::<Vec<_>>();
+        outputs.sort();
+        assert_eq!(outputs, ["DP-4", "eDP-1"], "{protocol:?}");
+
+        let dp = client.group(&output("DP-4")).unwrap();
+        let mut names = state
+            .workspaces
+            .iter()
+            .filter(|ws| ws.group.as_ref() == Some(&dp.handle))
+            .filter_map(|ws| ws.name.clone())
+            .collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, ["mail", "terminal", "web"], "{protocol:?}");
+
+        drop(client);
+        drop(conn);
+        compositor.stop();
+    }
+}
+
+#[test]
+fn protocol_detection() {
+    for protocol in PROTOCOLS {
+        let (compositor, conn) = MockCompositor::spawn(protocol, Layout::two_outputs());
+        let client = WorkspaceClient::from_connection(&conn, None).unwrap();
+        assert_eq!(client.state().protocol, protocol);
+        drop(client);
+        drop(conn);
+        compositor.stop();
+    }
+}
+
+#[test]
+fn several_requests_in_one_commit() {
+    for protocol in PROTOCOLS {
+        let (compositor, conn) = MockCompositor::spawn(protocol, Layout::two_outputs());
+        let mut client = WorkspaceClient::from_connection(&conn, Some(protocol)).unwrap();
+        client
+            .workspace(&workspace("mail"), Some(&output("DP-4")))
+            .unwrap()
+            .activate();
+        client.group(&output("eDP-1")).unwrap().create_workspace("scratch".to_string());
+        client.commit().unwrap();
+        drop(client);
+        drop(conn);
+
+        let layout = compositor.stop();
+        assert!(layout.workspace("mail").active, "{protocol:?}");
+        assert_eq!(layout.workspace("scratch").group, Some(0), "{protocol:?}");
+    }
+}
+
+#[test]
+fn stop() {
+    for protocol in PROTOCOLS {
+        let (compositor, conn) = MockCompositor::spawn(protocol, Layout::two_outputs());
+        let mut client = WorkspaceClient::from_connection(&conn, Some(protocol)).unwrap();
+        assert!(!client.is_finished());
+
+        client.stop().unwrap();
+        assert!(client.is_finished(), "{protocol:?}");
+        client.workspace(&workspace("mail"), None).unwrap().activate();
+        assert_eq!(client.commit(), Err(Error::Finished), "{protocol:?}");
+
+        let layout = compositor.stop();
+        assert!(!layout.workspace("mail").active, "{protocol:?}");
+    }
+}
+
+#[test]
+fn finished_by_compositor() {
+    for protocol in PROTOCOLS {
+        let (compositor, conn) = MockCompositor::spawn(protocol, Layout::two_outputs());
+        let mut client = WorkspaceClient::from_connection(&conn, Some(protocol)).unwrap();
+        compositor.finish();
+        while !client.is_finished() {
+            client.blocking_dispatch().unwrap();
+        }
+        // the last known state stays available
+        assert!(client.workspace(&workspace("mail"), None).is_ok(), "{protocol:?}");
+        assert_eq!(client.commit(), Err(Error::Finished), "{protocol:?}");
+        compositor.stop();
+    }
+}
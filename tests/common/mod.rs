@@ -0,0 +1,1037 @@
+// in-process compositor serving the workspace protocols from `resources/` on a private socket
+// every mock serves exactly one client and one protocol
+#![allow(dead_code)]
+use std::collections::HashMap;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::process::{Command, Output};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use wayland_client::Connection;
+use wayland_server::{
+    backend::ClientData,
+    protocol::wl_output::{self, WlOutput},
+    Client, DataInit, Dispatch, Display, DisplayHandle, GlobalDispatch, New, Resource, WEnum,
+};
+
+use wsctrl::workspace_state::Protocol;
+
+use self::server::{
+    cosmic_v1::{
+        zcosmic_workspace_group_handle_v1::{self, ZcosmicWorkspaceGroupHandleV1},
+        zcosmic_workspace_handle_v1::{self, ZcosmicWorkspaceHandleV1},
+        zcosmic_workspace_manager_v1::{self, ZcosmicWorkspaceManagerV1},
+    },
+    ext_v0::{
+        zext_workspace_group_handle_v1::{self, ZextWorkspaceGroupHandleV1},
+        zext_workspace_handle_v1::{self, ZextWorkspaceHandleV1},
+        zext_workspace_manager_v1::{self, ZextWorkspaceManagerV1},
+    },
+    ext_v1::{
+        ext_workspace_group_handle_v1::{self, ExtWorkspaceGroupHandleV1},
+        ext_workspace_handle_v1::{self, ExtWorkspaceHandleV1},
+        ext_workspace_manager_v1::{self, ExtWorkspaceManagerV1},
+    },
+};
+
+pub mod server {
+    #[allow(non_upper_case_globals, non_camel_case_types, dead_code)]
+    pub mod ext_v0 {
+        use wayland_server;
+        use wayland_server::protocol::*;
+        pub mod __interfaces {
+            use wayland_server::protocol::__interfaces::*;
+            wayland_scanner::generate_interfaces!("./resources/ext-workspace-unstable-v1.xml");
+        }
+        use self::__interfaces::*;
+        wayland_scanner::generate_server_code!("./resources/ext-workspace-unstable-v1.xml");
+    }
+    #[allow(non_upper_case_globals, non_camel_case_types, dead_code)]
+    pub mod ext_v1 {
+        use wayland_server;
+        use wayland_server::protocol::*;
+        pub mod __interfaces {
+            use wayland_server::protocol::__interfaces::*;
+            wayland_scanner::generate_interfaces!("./resources/ext-workspace-v1.xml");
+        }
+        use self::__interfaces::*;
+        wayland_scanner::generate_server_code!("./resources/ext-workspace-v1.xml");
+    }
+    #[allow(non_upper_case_globals, non_camel_case_types, dead_code)]
+    pub mod cosmic_v1 {
+        use wayland_server;
+        use wayland_server::protocol::*;
+        pub mod __interfaces {
+            use wayland_server::protocol::__interfaces::*;
+            wayland_scanner::generate_interfaces!("./resources/cosmic-workspace-unstable-v1.xml");
+        }
+        use self::__interfaces::*;
+        wayland_scanner::generate_server_code!("./resources/cosmic-workspace-unstable-v1.xml");
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MockOutput {
+    pub name: String,
+    pub description: String,
+    pub location: (i32, i32),
+    pub size: (i32, i32),
+}
+
+#[derive(Debug, Clone)]
+pub struct MockGroup {
+    pub output: Option<usize>,
+    pub removed: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MockWorkspace {
+    pub name: String,
+    pub id: Option<String>,
+    pub coordinates: Vec<u32>,
+    pub active: bool,
+    pub urgent: bool,
+    pub hidden: bool,
+    pub tiling: bool,
+    /// Does not advertise the cosmic-v1 set_tiling_state capability.
+    pub fixed_tiling: bool,
+    /// Does not advertise the ext-v1 assign capability.
+    pub fixed_output: bool,
+    pub group: Option<usize>,
+    pub removed: bool,
+}
+
+impl MockWorkspace {
+    // ext_v1 sends the state as bitfield
+    fn state_bits(&self) -> u32 {
+        (self.active as u32) | (self.urgent as u32) << 1 | (self.hidden as u32) << 2
+    }
+    // ext_v0 and cosmic_v1 send the state as array of enum values
+    fn state_array(&self) -> Vec<u8> {
+        let mut states = Vec::new();
+        if self.active {
+            states.push(0);
+        }
+        if self.urgent {
+            states.push(1);
+        }
+        if self.hidden {
+            states.push(2);
+        }
+        u32_array(&states)
+    }
+}
+
+fn tiling_state(tiling: bool) -> zcosmic_workspace_handle_v1::TilingState {
+    if tiling {
+        zcosmic_workspace_handle_v1::TilingState::TilingEnabled
+    } else {
+        zcosmic_workspace_handle_v1::TilingState::FloatingOnly
+    }
+}
+
+fn u32_array(values: &[u32]) -> Vec<u8> {
+    values.iter().flat_map(|v| v.to_ne_bytes()).collect()
+}
+
+/// Outputs, groups and workspaces the mock compositor announces. Groups are indexed like the outputs
+/// they were created for.
+#[derive(Debug, Clone, Default)]
+pub struct Layout {
+    pub outputs: Vec<MockOutput>,
+    pub groups: Vec<MockGroup>,
+    pub workspaces: Vec<MockWorkspace>,
+    /// Number of commit requests received from clients.
+    pub commits: usize,
+}
+
+impl Layout {
+    pub fn add_output(&mut self, name: &str, location: (i32, i32)) -> usize {
+        self.outputs.push(MockOutput {
+            name: name.to_string(),
+            description: format!("Mock - {name}"),
+            location,
+            size: (1920, 1080),
+        });
+        self.groups.push(MockGroup {
+            output: Some(self.outputs.len() - 1),
+            removed: false,
+        });
+        self.groups.len() - 1
+    }
+
+    pub fn add_workspace(&mut self, group: usize, name: &str) -> usize {
+        let coordinates = vec![self
+            .workspaces
+            .iter()
+            .filter(|ws| ws.group == Some(group))
+            .count() as u32];
+        self.workspaces.push(MockWorkspace {
+            name: name.to_string(),
+            id: Some(format!("mock-{}", self.workspaces.len())),
+            coordinates,
+            group: Some(group),
+            ..Default::default()
+        });
+        self.workspaces.len() - 1
+    }
+
+    /// eDP-1 with the active workspace "main", DP-4 with the active workspace "web" plus "mail" and "terminal".
+    pub fn two_outputs() -> Self {
+        let mut layout = Layout::default();
+        let edp = layout.add_output("eDP-1", (2560, 0));
+        let dp = layout.add_output("DP-4", (0, 0));
+        let main = layout.add_workspace(edp, "main");
+        let web = layout.add_workspace(dp, "web");
+        layout.add_workspace(dp, "mail");
+        layout.add_workspace(dp, "terminal");
+        layout.workspaces[main].active = true;
+        layout.workspaces[web].active = true;
+        layout
+    }
+
+    pub fn workspace(&self, name: &str) -> &MockWorkspace {
+        self.workspaces
+            .iter()
+            .find(|ws| !ws.removed && ws.name == name)
+            .unwrap_or_else(|| panic!("no workspace named {name}"))
+    }
+
+    pub fn has_workspace(&self, name: &str) -> bool {
+        self.workspaces.iter().any(|ws| !ws.removed && ws.name == name)
+    }
+}
+
+/// Change of the layout, requested by a client or applied by the compositor on its own.
+pub enum Change {
+    Activate(usize),
+    Deactivate(usize),
+    Remove(usize),
+    Assign(usize, usize),
+    Create(usize, String),
+    Rename(usize, String),
+    SetTiling(usize, bool),
+    Urgent(usize, bool),
+}
+
+enum ManagerResource {
+    ExtV0(ZextWorkspaceManagerV1),
+    ExtV1(ExtWorkspaceManagerV1),
+    CosmicV1(ZcosmicWorkspaceManagerV1),
+}
+
+impl ManagerResource {
+    fn client(&self) -> Option<Client> {
+        match self {
+            ManagerResource::ExtV0(manager) => manager.client(),
+            ManagerResource::ExtV1(manager) => manager.client(),
+            ManagerResource::CosmicV1(manager) => manager.client(),
+        }
+    }
+}
+
+enum GroupResource {
+    ExtV0(ZextWorkspaceGroupHandleV1),
+    ExtV1(ExtWorkspaceGroupHandleV1),
+    CosmicV1(ZcosmicWorkspaceGroupHandleV1),
+}
+
+enum WorkspaceResource {
+    ExtV0(ZextWorkspaceHandleV1),
+    ExtV1(ExtWorkspaceHandleV1),
+    CosmicV1(ZcosmicWorkspaceHandleV1),
+}
+
+struct MockState {
+    layout: Layout,
+    outputs: Vec<Vec<WlOutput>>,
+    manager: Option<ManagerResource>,
+    groups: HashMap<usize, GroupResource>,
+    workspaces: HashMap<usize, WorkspaceResource>,
+    pending: Vec<Change>,
+    controls: Arc<Controls>,
+}
+
+struct MockClient;
+impl ClientData for MockClient {}
+
+// flags the test thread sets to steer the compositor thread
+#[derive(Default)]
+struct Controls {
+    stop: AtomicBool,
+    finish: AtomicBool,
+    ignore_requests: AtomicBool,
+    changes: Mutex<Vec<Change>>,
+}
+
+/// Compositor running on its own thread until [`MockCompositor::stop`] is called.
+pub struct MockCompositor {
+    controls: Arc<Controls>,
+    thread: JoinHandle<Layout>,
+    socket_path: Option<PathBuf>,
+}
+
+enum MockSocket {
+    Stream(UnixStream),
+    Listener(UnixListener),
+}
+
+impl MockCompositor {
+    /// Spawns a compositor advertising the given workspace protocol and returns a connection to it.
+    pub fn spawn(protocol: Protocol, layout: Layout) -> (MockCompositor, Connection) {
+        let (server_socket, client_socket) =
+            UnixStream::pair().expect("failed to create socket pair");
+        let compositor = Self::start(
+            protocol,
+            max_version(protocol),
+            layout,
+            MockSocket::Stream(server_socket),
+            None,
+        );
+        let conn = Connection::from_socket(client_socket).expect("failed to connect to mock");
+        (compositor, conn)
+    }
+
+    /// Spawns a compositor listening on a socket in the temp dir, usable as `WAYLAND_DISPLAY`.
+    pub fn listen(protocol: Protocol, layout: Layout) -> MockCompositor {
+        Self::listen_version(protocol, max_version(protocol), layout)
+    }
+
+    /// Like [`MockCompositor::listen`], but advertises the workspace manager with the given version.
+    pub fn listen_version(protocol: Protocol, version: u32, layout: Layout) -> MockCompositor {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let socket_path = std::env::temp_dir().join(format!(
+            "wsctrl-test-{}-{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = std::fs::remove_file(&socket_path);
+        let listener = UnixListener::bind(&socket_path).expect("failed to bind mock socket");
+        listener
+            .set_nonblocking(true)
+            .expect("failed to configure mock socket");
+        Self::start(
+            protocol,
+            version,
+            layout,
+            MockSocket::Listener(listener),
+            Some(socket_path),
+        )
+    }
+
+    fn start(
+        protocol: Protocol,
+        version: u32,
+        layout: Layout,
+        socket: MockSocket,
+        socket_path: Option<PathBuf>,
+    ) -> MockCompositor {
+        let controls = Arc::new(Controls::default());
+        let thread = {
+            let controls = controls.clone();
+            thread::spawn(move || run(protocol, version, layout, socket, controls))
+        };
+        MockCompositor {
+            controls,
+            thread,
+            socket_path,
+        }
+    }
+
+    /// Socket path of the listening compositor, as passed in `WAYLAND_DISPLAY`.
+    pub fn display(&self) -> &str {
+        self.socket_path
+            .as_ref()
+            .and_then(|path| path.to_str())
+            .expect("compositor is not listening")
+    }
+
+    /// Runs the wsctrl binary against the listening compositor.
+    pub fn wsctrl(&self, args: &[&str]) -> Output {
+        self.command(args).output().expect("failed to run wsctrl")
+    }
+
+    /// wsctrl command connecting to the listening compositor, for commands that keep running.
+    pub fn command(&self, args: &[&str]) -> Command {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_wsctrl"));
+        command
+            .args(args)
+            .env(
+                "WAYLAND_DISPLAY",
+                self.socket_path.as_ref().expect("compositor is not listening"),
+            )
+            .env_remove("WAYLAND_SOCKET");
+        command
+    }
+
+    /// Sends `finished` on the workspace manager as soon as the client bound it.
+    pub fn finish(&self) {
+        self.controls.finish.store(true, Ordering::SeqCst);
+    }
+
+    /// Answers commits with `done` without applying the requested changes.
+    pub fn ignore_requests(&self) {
+        self.controls.ignore_requests.store(true, Ordering::Relaxed);
+    }
+
+    /// Applies the change and sends `done`, without a request of the client. Waits until the client
+    /// bound the workspace manager. Changes applied before [`MockCompositor::finish`] are sent
+    /// before `finished`.
+    pub fn apply(&self, change: Change) {
+        self.controls.changes.lock().unwrap().push(change);
+    }
+
+    /// Stops the compositor and returns the layout after all committed changes.
+    pub fn stop(self) -> Layout {
+        self.controls.stop.store(true, Ordering::Relaxed);
+        let layout = self.thread.join().expect("mock compositor panicked");
+        if let Some(socket_path) = self.socket_path {
+            let _ = std::fs::remove_file(socket_path);
+        }
+        layout
+    }
+}
+
+fn max_version(protocol: Protocol) -> u32 {
+    match protocol {
+        Protocol::ExtV0 | Protocol::ExtV1 => 1,
+        Protocol::CosmicV1 => 2,
+    }
+}
+
+fn run(
+    protocol: Protocol,
+    version: u32,
+    layout: Layout,
+    socket: MockSocket,
+    controls: Arc<Controls>,
+) -> Layout {
+    let mut display = Display::<MockState>::new().expect("failed to create display");
+    let mut handle = display.handle();
+    for output in 0..layout.outputs.len() {
+        handle.create_global::<MockState, WlOutput, usize>(4, output);
+    }
+    match protocol {
+        Protocol::ExtV0 => {
+            handle.create_global::<MockState, ZextWorkspaceManagerV1, ()>(version, ())
+        }
+        Protocol::ExtV1 => {
+            handle.create_global::<MockState, ExtWorkspaceManagerV1, ()>(version, ())
+        }
+        Protocol::CosmicV1 => {
+            handle.create_global::<MockState, ZcosmicWorkspaceManagerV1, ()>(version, ())
+        }
+    };
+    let mut listener = match socket {
+        MockSocket::Stream(stream) => {
+            handle
+                .insert_client(stream, Arc::new(MockClient))
+                .expect("failed to insert client");
+            None
+        }
+        MockSocket::Listener(listener) => Some(listener),
+    };
+
+    let mut state = MockState {
+        outputs: vec![Vec::new(); layout.outputs.len()],
+        layout,
+        manager: None,
+        groups: HashMap::new(),
+        workspaces: HashMap::new(),
+        pending: Vec::new(),
+        controls,
+    };
+    while !state.controls.stop.load(Ordering::Relaxed) {
+        if let Some(Ok((stream, _))) = listener.as_ref().map(|l| l.accept()) {
+            handle
+                .insert_client(stream, Arc::new(MockClient))
+                .expect("failed to insert client");
+            listener = None;
+        }
+        display
+            .dispatch_clients(&mut state)
+            .expect("failed to dispatch clients");
+        // read before the changes, so changes applied before finish are sent before it
+        let finish = state.controls.finish.load(Ordering::SeqCst);
+        let client = state.manager.as_ref().and_then(|manager| manager.client());
+        let changes = std::mem::take(&mut *state.controls.changes.lock().unwrap());
+        if let (Some(client), false) = (client, changes.is_empty()) {
+            state.pending.extend(changes);
+            state.apply_pending(&handle, &client);
+        } else {
+            state.controls.changes.lock().unwrap().splice(0..0, changes);
+        }
+        if state.manager.is_some() && finish {
+            state.controls.finish.store(false, Ordering::SeqCst);
+            state.send_finished();
+        }
+        // the client may already be gone
+        let _ = display.flush_clients();
+        thread::sleep(Duration::from_millis(1));
+    }
+    state.layout
+}
+
+impl MockState {
+    fn announce_group(&mut self, dh: &DisplayHandle, client: &Client, group: usize) {
+        let resource = match &self.manager {
+            Some(ManagerResource::ExtV0(manager)) => {
+                let handle = client
+                    .create_resource::<ZextWorkspaceGroupHandleV1, usize, MockState>(
+                        dh,
+                        manager.version(),
+                        group,
+                    )
+                    .unwrap();
+                manager.workspace_group(&handle);
+                GroupResource::ExtV0(handle)
+            }
+            Some(ManagerResource::ExtV1(manager)) => {
+                let handle = client
+                    .create_resource::<ExtWorkspaceGroupHandleV1, usize, MockState>(
+                        dh,
+                        manager.version(),
+                        group,
+                    )
+                    .unwrap();
+                manager.workspace_group(&handle);
+                handle.capabilities(ext_workspace_group_handle_v1::GroupCapabilities::CreateWorkspace);
+                GroupResource::ExtV1(handle)
+            }
+            Some(ManagerResource::CosmicV1(manager)) => {
+                let handle = client
+                    .create_resource::<ZcosmicWorkspaceGroupHandleV1, usize, MockState>(
+                        dh,
+                        manager.version(),
+                        group,
+                    )
+                    .unwrap();
+                manager.workspace_group(&handle);
+                handle.capabilities(u32_array(&[1]));
+                GroupResource::CosmicV1(handle)
+            }
+            None => return,
+        };
+        if let Some(output) = self.layout.groups[group].output {
+            for wl_output in &self.outputs[output] {
+                match &resource {
+                    GroupResource::ExtV0(handle) => handle.output_enter(wl_output),
+                    GroupResource::ExtV1(handle) => handle.output_enter(wl_output),
+                    GroupResource::CosmicV1(handle) => handle.output_enter(wl_output),
+                }
+            }
+        }
+        self.groups.insert(group, resource);
+    }
+
+    fn announce_workspace(&mut self, dh: &DisplayHandle, client: &Client, workspace: usize) {
+        let ws = self.layout.workspaces[workspace].clone();
+        let group = ws.group.and_then(|g| self.groups.get(&g));
+        let resource = match (&self.manager, group) {
+            (Some(ManagerResource::ExtV0(_)), Some(GroupResource::ExtV0(group))) => {
+                let handle = client
+                    .create_resource::<ZextWorkspaceHandleV1, usize, MockState>(
+                        dh,
+                        group.version(),
+                        workspace,
+                    )
+                    .unwrap();
+                group.workspace(&handle);
+                handle.name(ws.name.clone());
+                handle.coordinates(u32_array(&ws.coordinates));
+                handle.state(ws.state_array());
+                WorkspaceResource::ExtV0(handle)
+            }
+            (Some(ManagerResource::ExtV1(manager)), group) => {
+                let handle = client
+                    .create_resource::<ExtWorkspaceHandleV1, usize, MockState>(
+                        dh,
+                        manager.version(),
+                        workspace,
+                    )
+                    .unwrap();
+                manager.workspace(&handle);
+                if let Some(id) = &ws.id {
+                    handle.id(id.clone());
+                }
+                handle.name(ws.name.clone());
+                handle.coordinates(u32_array(&ws.coordinates));
+                handle.state(ext_workspace_handle_v1::State::from_bits_truncate(ws.state_bits()));
+                let mut capabilities = ext_workspace_handle_v1::WorkspaceCapabilities::all();
+                if ws.fixed_output {
+                    capabilities.remove(ext_workspace_handle_v1::WorkspaceCapabilities::Assign);
+                }
+                handle.capabilities(capabilities);
+                if let Some(GroupResource::ExtV1(group)) = group {
+                    group.workspace_enter(&handle);
+                }
+                WorkspaceResource::ExtV1(handle)
+            }
+            (Some(ManagerResource::CosmicV1(_)), Some(GroupResource::CosmicV1(group))) => {
+                let handle = client
+                    .create_resource::<ZcosmicWorkspaceHandleV1, usize, MockState>(
+                        dh,
+                        group.version(),
+                        workspace,
+                    )
+                    .unwrap();
+                group.workspace(&handle);
+                handle.name(ws.name.clone());
+                handle.coordinates(u32_array(&ws.coordinates));
+                handle.state(ws.state_array());
+                if handle.version() >= 2 && ws.fixed_tiling {
+                    handle.capabilities(u32_array(&[1, 2, 3, 4]));
+                    handle.tiling_state(tiling_state(ws.tiling));
+                } else if handle.version() >= 2 {
+                    handle.capabilities(u32_array(&[1, 2, 3, 4, 5]));
+                    handle.tiling_state(tiling_state(ws.tiling));
+                } else {
+                    handle.capabilities(u32_array(&[1, 2, 3]));
+                }
+                WorkspaceResource::CosmicV1(handle)
+            }
+            // ext_v0 and cosmic_v1 can only announce workspaces through a group
+            _ => return,
+        };
+        self.workspaces.insert(workspace, resource);
+    }
+
+    fn send_state(&self, workspace: usize) {
+        let ws = &self.layout.workspaces[workspace];
+        match self.workspaces.get(&workspace) {
+            Some(WorkspaceResource::ExtV0(handle)) => handle.state(ws.state_array()),
+            Some(WorkspaceResource::ExtV1(handle)) => handle.state(
+                ext_workspace_handle_v1::State::from_bits_truncate(ws.state_bits()),
+            ),
+            Some(WorkspaceResource::CosmicV1(handle)) => handle.state(ws.state_array()),
+            None => {}
+        }
+    }
+
+    fn send_tiling_state(&self, workspace: usize) {
+        if let Some(WorkspaceResource::CosmicV1(handle)) = self.workspaces.get(&workspace) {
+            handle.tiling_state(tiling_state(self.layout.workspaces[workspace].tiling));
+        }
+    }
+
+    fn send_name(&self, workspace: usize) {
+        let name = self.layout.workspaces[workspace].name.clone();
+        match self.workspaces.get(&workspace) {
+            Some(WorkspaceResource::ExtV0(handle)) => handle.name(name),
+            Some(WorkspaceResource::ExtV1(handle)) => handle.name(name),
+            Some(WorkspaceResource::CosmicV1(handle)) => handle.name(name),
+            None => {}
+        }
+    }
+
+    fn send_removed(&mut self, workspace: usize) {
+        if let (Some(group), Some(WorkspaceResource::ExtV1(handle))) = (
+            self.layout.workspaces[workspace].group,
+            self.workspaces.get(&workspace),
+        ) {
+            if let Some(GroupResource::ExtV1(group)) = self.groups.get(&group) {
+                group.workspace_leave(handle);
+            }
+        }
+        match self.workspaces.remove(&workspace) {
+            Some(WorkspaceResource::ExtV0(handle)) => handle.remove(),
+            Some(WorkspaceResource::ExtV1(handle)) => handle.removed(),
+            Some(WorkspaceResource::CosmicV1(handle)) => handle.remove(),
+            None => {}
+        }
+    }
+
+    fn send_done(&self) {
+        match &self.manager {
+            Some(ManagerResource::ExtV0(manager)) => manager.done(),
+            Some(ManagerResource::ExtV1(manager)) => manager.done(),
+            Some(ManagerResource::CosmicV1(manager)) => manager.done(),
+            None => {}
+        }
+    }
+
+    fn send_finished(&mut self) {
+        match self.manager.take() {
+            Some(ManagerResource::ExtV0(manager)) => manager.finished(),
+            Some(ManagerResource::ExtV1(manager)) => manager.finished(),
+            Some(ManagerResource::CosmicV1(manager)) => manager.finished(),
+            None => {}
+        }
+    }
+
+    fn announce_all(&mut self, dh: &DisplayHandle, client: &Client) {
+        for group in 0..self.layout.groups.len() {
+            if !self.layout.groups[group].removed {
+                self.announce_group(dh, client, group);
+            }
+        }
+        for workspace in 0..self.layout.workspaces.len() {
+            if !self.layout.workspaces[workspace].removed {
+                self.announce_workspace(dh, client, workspace);
+            }
+        }
+        self.send_done();
+    }
+
+    fn commit(&mut self, dh: &DisplayHandle, client: &Client) {
+        self.layout.commits += 1;
+        if self.controls.ignore_requests.load(Ordering::Relaxed) {
+            self.pending.clear();
+        }
+        self.apply_pending(dh, client);
+    }
+
+    fn apply_pending(&mut self, dh: &DisplayHandle, client: &Client) {
+        for change in std::mem::take(&mut self.pending) {
+            match change {
+                Change::Activate(workspace) => {
+                    let group = self.layout.workspaces[workspace].group;
+                    for other in 0..self.layout.workspaces.len() {
+                        let ws = &mut self.layout.workspaces[other];
+                        if other != workspace && ws.active && ws.group == group {
+                            ws.active = false;
+                            self.send_state(other);
+                        }
+                    }
+                    self.layout.workspaces[workspace].active = true;
+                    self.send_state(workspace);
+                }
+                Change::Deactivate(workspace) => {
+                    self.layout.workspaces[workspace].active = false;
+                    self.send_state(workspace);
+                }
+                Change::Remove(workspace) => {
+                    self.send_removed(workspace);
+                    self.layout.workspaces[workspace].removed = true;
+                }
+                Change::Assign(workspace, group) => {
+                    let old = self.layout.workspaces[workspace].group.replace(group);
+                    if let Some(WorkspaceResource::ExtV1(handle)) = self.workspaces.get(&workspace) {
+                        if let Some(GroupResource::ExtV1(old)) = old.and_then(|g| self.groups.get(&g)) {
+                            old.workspace_leave(handle);
+                        }
+                        if let Some(GroupResource::ExtV1(new)) = self.groups.get(&group) {
+                            new.workspace_enter(handle);
+                        }
+                    }
+                }
+                Change::Create(group, name) => {
+                    let workspace = self.layout.add_workspace(group, &name);
+                    self.announce_workspace(dh, client, workspace);
+                }
+                Change::Rename(workspace, name) => {
+                    self.layout.workspaces[workspace].name = name;
+                    self.send_name(workspace);
+                }
+                Change::SetTiling(workspace, tiling) => {
+                    self.layout.workspaces[workspace].tiling = tiling;
+                    self.send_tiling_state(workspace);
+                }
+                Change::Urgent(workspace, urgent) => {
+                    self.layout.workspaces[workspace].urgent = urgent;
+                    self.send_state(workspace);
+                }
+            }
+        }
+        self.send_done();
+    }
+}
+
+impl GlobalDispatch<WlOutput, usize> for MockState {
+    fn bind(
+        state: &mut Self,
+        _handle: &DisplayHandle,
+        _client: &Client,
+        resource: New<WlOutput>,
+        output: &usize,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        let wl_output = data_init.init(resource, *output);
+        let info = &state.layout.outputs[*output];
+        wl_output.geometry(
+            info.location.0,
+            info.location.1,
+            info.size.0 / 4,
+            info.size.1 / 4,
+            wl_output::Subpixel::Unknown,
+            "Mock".to_string(),
+            info.name.clone(),
+            wl_output::Transform::Normal,
+        );
+        wl_output.mode(wl_output::Mode::Current, info.size.0, info.size.1, 60000);
+        if wl_output.version() >= 4 {
+            wl_output.name(info.name.clone());
+            wl_output.description(info.description.clone());
+        }
+        if wl_output.version() >= 2 {
+            wl_output.done();
+        }
+        state.outputs[*output].push(wl_output);
+    }
+}
+
+impl Dispatch<WlOutput, usize> for MockState {
+    fn request(
+        _state: &mut Self,
+        _client: &Client,
+        _resource: &WlOutput,
+        _request: wl_output::Request,
+        _data: &usize,
+        _dhandle: &DisplayHandle,
+        _data_init: &mut DataInit<'_, Self>,
+    ) {
+    }
+}
+
+impl GlobalDispatch<ZextWorkspaceManagerV1, ()> for MockState {
+    fn bind(
+        state: &mut Self,
+        handle: &DisplayHandle,
+        client: &Client,
+        resource: New<ZextWorkspaceManagerV1>,
+        _global_data: &(),
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        state.manager = Some(ManagerResource::ExtV0(data_init.init(resource, ())));
+        state.announce_all(handle, client);
+    }
+}
+
+impl GlobalDispatch<ExtWorkspaceManagerV1, ()> for MockState {
+    fn bind(
+        state: &mut Self,
+        handle: &DisplayHandle,
+        client: &Client,
+        resource: New<ExtWorkspaceManagerV1>,
+        _global_data: &(),
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        state.manager = Some(ManagerResource::ExtV1(data_init.init(resource, ())));
+        state.announce_all(handle, client);
+    }
+}
+
+impl GlobalDispatch<ZcosmicWorkspaceManagerV1, ()> for MockState {
+    fn bind(
+        state: &mut Self,
+        handle: &DisplayHandle,
+        client: &Client,
+        resource: New<ZcosmicWorkspaceManagerV1>,
+        _global_data: &(),
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        state.manager = Some(ManagerResource::CosmicV1(data_init.init(resource, ())));
+        state.announce_all(handle, client);
+    }
+}
+
+impl Dispatch<ZextWorkspaceManagerV1, ()> for MockState {
+    fn request(
+        state: &mut Self,
+        client: &Client,
+        _resource: &ZextWorkspaceManagerV1,
+        request: zext_workspace_manager_v1::Request,
+        _data: &(),
+        dhandle: &DisplayHandle,
+        _data_init: &mut DataInit<'_, Self>,
+    ) {
+        match request {
+            zext_workspace_manager_v1::Request::Commit => state.commit(dhandle, client),
+            zext_workspace_manager_v1::Request::Stop => state.send_finished(),
+        }
+    }
+}
+
+impl Dispatch<ExtWorkspaceManagerV1, ()> for MockState {
+    fn request(
+        state: &mut Self,
+        client: &Client,
+        _resource: &ExtWorkspaceManagerV1,
+        request: ext_workspace_manager_v1::Request,
+        _data: &(),
+        dhandle: &DisplayHandle,
+        _data_init: &mut DataInit<'_, Self>,
+    ) {
+        match request {
+            ext_workspace_manager_v1::Request::Commit => state.commit(dhandle, client),
+            ext_workspace_manager_v1::Request::Stop => state.send_finished(),
+        }
+    }
+}
+
+impl Dispatch<ZcosmicWorkspaceManagerV1, ()> for MockState {
+    fn request(
+        state: &mut Self,
+        client: &Client,
+        _resource: &ZcosmicWorkspaceManagerV1,
+        request: zcosmic_workspace_manager_v1::Request,
+        _data: &(),
+        dhandle: &DisplayHandle,
+        _data_init: &mut DataInit<'_, Self>,
+    ) {
+        match request {
+            zcosmic_workspace_manager_v1::Request::Commit => state.commit(dhandle, client),
+            zcosmic_workspace_manager_v1::Request::Stop => state.send_finished(),
+        }
+    }
+}
+
+impl Dispatch<ZextWorkspaceGroupHandleV1, usize> for MockState {
+    fn request(
+        state: &mut Self,
+        _client: &Client,
+        _resource: &ZextWorkspaceGroupHandleV1,
+        request: zext_workspace_group_handle_v1::Request,
+        group: &usize,
+        _dhandle: &DisplayHandle,
+        _data_init: &mut DataInit<'_, Self>,
+    ) {
+        match request {
+            zext_workspace_group_handle_v1::Request::CreateWorkspace { workspace } => {
+                state.pending.push(Change::Create(*group, workspace))
+            }
+            zext_workspace_group_handle_v1::Request::Destroy => {
+                state.groups.remove(group);
+            }
+        }
+    }
+}
+
+impl Dispatch<ExtWorkspaceGroupHandleV1, usize> for MockState {
+    fn request(
+        state: &mut Self,
+        _client: &Client,
+        _resource: &ExtWorkspaceGroupHandleV1,
+        request: ext_workspace_group_handle_v1::Request,
+        group: &usize,
+        _dhandle: &DisplayHandle,
+        _data_init: &mut DataInit<'_, Self>,
+    ) {
+        match request {
+            ext_workspace_group_handle_v1::Request::CreateWorkspace { workspace } => {
+                state.pending.push(Change::Create(*group, workspace))
+            }
+            ext_workspace_group_handle_v1::Request::Destroy => {
+                state.groups.remove(group);
+            }
+        }
+    }
+}
+
+impl Dispatch<ZcosmicWorkspaceGroupHandleV1, usize> for MockState {
+    fn request(
+        state: &mut Self,
+        _client: &Client,
+        _resource: &ZcosmicWorkspaceGroupHandleV1,
+        request: zcosmic_workspace_group_handle_v1::Request,
+        group: &usize,
+        _dhandle: &DisplayHandle,
+        _data_init: &mut DataInit<'_, Self>,
+    ) {
+        match request {
+            zcosmic_workspace_group_handle_v1::Request::CreateWorkspace { workspace } => {
+                state.pending.push(Change::Create(*group, workspace))
+            }
+            zcosmic_workspace_group_handle_v1::Request::Destroy => {
+                state.groups.remove(group);
+            }
+        }
+    }
+}
+
+impl Dispatch<ZextWorkspaceHandleV1, usize> for MockState {
+    fn request(
+        state: &mut Self,
+        _client: &Client,
+        _resource: &ZextWorkspaceHandleV1,
+        request: zext_workspace_handle_v1::Request,
+        workspace: &usize,
+        _dhandle: &DisplayHandle,
+        _data_init: &mut DataInit<'_, Self>,
+    ) {
+        match request {
+            zext_workspace_handle_v1::Request::Activate => {
+                state.pending.push(Change::Activate(*workspace))
+            }
+            zext_workspace_handle_v1::Request::Deactivate => {
+                state.pending.push(Change::Deactivate(*workspace))
+            }
+            zext_workspace_handle_v1::Request::Remove => {
+                state.pending.push(Change::Remove(*workspace))
+            }
+            zext_workspace_handle_v1::Request::Destroy => {
+                state.workspaces.remove(workspace);
+            }
+        }
+    }
+}
+
+impl Dispatch<ExtWorkspaceHandleV1, usize> for MockState {
+    fn request(
+        state: &mut Self,
+        _client: &Client,
+        _resource: &ExtWorkspaceHandleV1,
+        request: ext_workspace_handle_v1::Request,
+        workspace: &usize,
+        _dhandle: &DisplayHandle,
+        _data_init: &mut DataInit<'_, Self>,
+    ) {
+        match request {
+            ext_workspace_handle_v1::Request::Activate => {
+                state.pending.push(Change::Activate(*workspace))
+            }
+            ext_workspace_handle_v1::Request::Deactivate => {
+                state.pending.push(Change::Deactivate(*workspace))
+            }
+            ext_workspace_handle_v1::Request::Remove => {
+                state.pending.push(Change::Remove(*workspace))
+            }
+            ext_workspace_handle_v1::Request::Assign { workspace_group } => {
+                if let Some(group) = workspace_group.data::<usize>() {
+                    state.pending.push(Change::Assign(*workspace, *group));
+                }
+            }
+            ext_workspace_handle_v1::Request::Destroy => {
+                state.workspaces.remove(workspace);
+            }
+        }
+    }
+}
+
+impl Dispatch<ZcosmicWorkspaceHandleV1, usize> for MockState {
+    fn request(
+        state: &mut Self,
+        _client: &Client,
+        _resource: &ZcosmicWorkspaceHandleV1,
+        request: zcosmic_workspace_handle_v1::Request,
+        workspace: &usize,
+        _dhandle: &DisplayHandle,
+        _data_init: &mut DataInit<'_, Self>,
+    ) {
+        match request {
+            zcosmic_workspace_handle_v1::Request::Activate => {
+                state.pending.push(Change::Activate(*workspace))
+            }
+            zcosmic_workspace_handle_v1::Request::Deactivate => {
+                state.pending.push(Change::Deactivate(*workspace))
+            }
+            zcosmic_workspace_handle_v1::Request::Remove => {
+                state.pending.push(Change::Remove(*workspace))
+            }
+            zcosmic_workspace_handle_v1::Request::Rename { name } => {
+                state.pending.push(Change::Rename(*workspace, name))
+            }
+            zcosmic_workspace_handle_v1::Request::SetTilingState { state: tiling_state } => {
+                let tiling = tiling_state
+                    == WEnum::Value(zcosmic_workspace_handle_v1::TilingState::TilingEnabled);
+                state.pending.push(Change::SetTiling(*workspace, tiling))
+            }
+            zcosmic_workspace_handle_v1::Request::Destroy => {
+                state.workspaces.remove(workspace);
+            }
+        }
+    }
+}